@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE refresh_runs (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    triggered_by VARCHAR(32) NOT NULL,
+    status VARCHAR(32) NOT NULL,
+    countries_processed INT NOT NULL,
+    error TEXT NULL,
+    started_at DATETIME NOT NULL,
+    finished_at DATETIME NOT NULL
+);
\ No newline at end of file
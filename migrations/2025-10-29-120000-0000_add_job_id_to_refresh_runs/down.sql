@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE refresh_runs DROP COLUMN job_id;
\ No newline at end of file
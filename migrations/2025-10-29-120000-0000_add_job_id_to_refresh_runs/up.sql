@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE refresh_runs
+    ADD COLUMN job_id VARCHAR(36) NOT NULL DEFAULT '' AFTER id,
+    ADD INDEX idx_refresh_runs_job_id (job_id);
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE refresh_runs
+    DROP INDEX idx_refresh_runs_status,
+    MODIFY finished_at DATETIME NOT NULL;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE refresh_runs
+    MODIFY finished_at DATETIME NULL,
+    ADD INDEX idx_refresh_runs_status (status);
\ No newline at end of file
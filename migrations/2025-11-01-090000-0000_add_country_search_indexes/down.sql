@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE countries
+    DROP INDEX idx_countries_region,
+    DROP INDEX idx_countries_currency_code;
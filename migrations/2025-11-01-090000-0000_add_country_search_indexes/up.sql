@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE countries
+    ADD INDEX idx_countries_region (region),
+    ADD INDEX idx_countries_currency_code (currency_code);
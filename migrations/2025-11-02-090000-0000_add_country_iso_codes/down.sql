@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE countries
+    DROP INDEX idx_countries_alpha2_code,
+    DROP INDEX idx_countries_alpha3_code,
+    DROP INDEX idx_countries_numeric_code,
+    DROP COLUMN alpha2_code,
+    DROP COLUMN alpha3_code,
+    DROP COLUMN numeric_code;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+ALTER TABLE countries
+    ADD COLUMN alpha2_code VARCHAR(2) NULL,
+    ADD COLUMN alpha3_code VARCHAR(3) NULL,
+    ADD COLUMN numeric_code VARCHAR(3) NULL,
+    ADD INDEX idx_countries_alpha2_code (alpha2_code),
+    ADD INDEX idx_countries_alpha3_code (alpha3_code),
+    ADD INDEX idx_countries_numeric_code (numeric_code);
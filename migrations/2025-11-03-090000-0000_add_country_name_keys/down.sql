@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE country_aliases;
+ALTER TABLE countries
+    DROP INDEX idx_countries_name_key,
+    DROP COLUMN name_key;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- `name_key` is the folded form of `name` that lookups compare against. LOWER() is only a first
+-- approximation for existing rows; the next refresh rewrites every key with accents and punctuation removed.
+ALTER TABLE countries
+    ADD COLUMN name_key VARCHAR(191) NULL,
+    ADD INDEX idx_countries_name_key (name_key);
+UPDATE countries SET name_key = LOWER(name);
+
+CREATE TABLE country_aliases (
+  country_id INT NOT NULL,
+  alias_key VARCHAR(191) NOT NULL,
+  PRIMARY KEY (country_id, alias_key),
+  INDEX idx_country_aliases_alias_key (alias_key),
+  CONSTRAINT fk_country_aliases_country FOREIGN KEY (country_id) REFERENCES countries (id) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci;
@@ -0,0 +1,38 @@
+-- Your SQL goes here
+-- PostgreSQL equivalent of the MySQL schema in `migrations/`.
+CREATE TABLE countries (
+  id SERIAL PRIMARY KEY,
+  name VARCHAR(191) NULL,
+  capital VARCHAR(191) NULL,
+  region VARCHAR(191) NULL,
+  population BIGINT NULL,
+  currency_code VARCHAR(32) NULL,
+  exchange_rate DOUBLE PRECISION NULL,
+  estimated_gdp DOUBLE PRECISION NULL,
+  flag_url VARCHAR(255) NULL,
+  last_refreshed_at TIMESTAMP NULL,
+
+  CONSTRAINT uniq_name UNIQUE (name)
+);
+
+CREATE TABLE cache_metadata (
+    id SERIAL PRIMARY KEY,
+    file_path VARCHAR(255) NOT NULL,
+    total_countries INT NOT NULL,
+    top_countries_json TEXT NOT NULL,
+    last_refreshed_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE refresh_runs (
+    id SERIAL PRIMARY KEY,
+    job_id VARCHAR(36) NOT NULL DEFAULT '',
+    triggered_by VARCHAR(32) NOT NULL,
+    status VARCHAR(32) NOT NULL,
+    countries_processed INT NOT NULL,
+    error TEXT NULL,
+    started_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP NULL
+);
+
+CREATE INDEX idx_refresh_runs_job_id ON refresh_runs (job_id);
+CREATE INDEX idx_refresh_runs_status ON refresh_runs (status);
\ No newline at end of file
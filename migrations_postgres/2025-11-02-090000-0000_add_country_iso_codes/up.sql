@@ -0,0 +1,7 @@
+-- Your SQL goes here
+ALTER TABLE countries ADD COLUMN alpha2_code VARCHAR(2);
+ALTER TABLE countries ADD COLUMN alpha3_code VARCHAR(3);
+ALTER TABLE countries ADD COLUMN numeric_code VARCHAR(3);
+CREATE INDEX idx_countries_alpha2_code ON countries (alpha2_code);
+CREATE INDEX idx_countries_alpha3_code ON countries (alpha3_code);
+CREATE INDEX idx_countries_numeric_code ON countries (numeric_code);
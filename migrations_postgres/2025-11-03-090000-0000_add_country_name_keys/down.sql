@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE country_aliases;
+DROP INDEX idx_countries_name_key;
+ALTER TABLE countries DROP COLUMN name_key;
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+CREATE TABLE currencies (
+  code VARCHAR(32) NOT NULL PRIMARY KEY,
+  name VARCHAR(191) NULL,
+  symbol VARCHAR(32) NULL,
+  exchange_rate DOUBLE PRECISION NULL,
+  rate_updated_at TIMESTAMP NULL
+);
+
+-- Every currency a country uses, in upstream order; `position` 0 is the one `countries.currency_code` holds.
+CREATE TABLE country_currencies (
+  country_id INTEGER NOT NULL REFERENCES countries (id) ON DELETE CASCADE,
+  currency_code VARCHAR(32) NOT NULL REFERENCES currencies (code),
+  position INTEGER NOT NULL,
+  PRIMARY KEY (country_id, currency_code)
+);
+CREATE INDEX idx_country_currencies_currency_code ON country_currencies (currency_code);
+
+-- Existing rows only know their primary currency until the next refresh.
+INSERT INTO currencies (code, exchange_rate, rate_updated_at)
+SELECT currency_code, MAX(exchange_rate), MAX(last_refreshed_at) FROM countries
+WHERE currency_code IS NOT NULL GROUP BY currency_code;
+INSERT INTO country_currencies (country_id, currency_code, position)
+SELECT id, currency_code, 0 FROM countries WHERE currency_code IS NOT NULL;
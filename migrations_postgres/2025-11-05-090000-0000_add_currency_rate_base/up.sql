@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- The currency `exchange_rate` is quoted against: units of this currency per one `rate_base`.
+ALTER TABLE currencies ADD COLUMN rate_base VARCHAR(32) NULL;
+-- Every rate stored so far came from the default USD endpoint.
+UPDATE currencies SET rate_base = 'USD' WHERE exchange_rate IS NOT NULL;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE refresh_runs;
+DROP TABLE cache_metadata;
+DROP TABLE countries;
\ No newline at end of file
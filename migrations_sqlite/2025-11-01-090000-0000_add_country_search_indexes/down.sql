@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_countries_region;
+DROP INDEX idx_countries_currency_code;
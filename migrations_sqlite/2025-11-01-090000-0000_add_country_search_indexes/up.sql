@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Region filters compare lower(region), so index the expression rather than the column.
+CREATE INDEX idx_countries_region ON countries (lower(region));
+CREATE INDEX idx_countries_currency_code ON countries (currency_code);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_countries_alpha2_code;
+DROP INDEX idx_countries_alpha3_code;
+DROP INDEX idx_countries_numeric_code;
+ALTER TABLE countries DROP COLUMN alpha2_code;
+ALTER TABLE countries DROP COLUMN alpha3_code;
+ALTER TABLE countries DROP COLUMN numeric_code;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- `name_key` is the folded form of `name` that lookups compare against. lower() is only a first
+-- approximation for existing rows; the next refresh rewrites every key with accents and punctuation removed.
+ALTER TABLE countries ADD COLUMN name_key VARCHAR(191);
+UPDATE countries SET name_key = lower(name);
+CREATE INDEX idx_countries_name_key ON countries (name_key);
+
+CREATE TABLE country_aliases (
+  country_id INTEGER NOT NULL REFERENCES countries (id) ON DELETE CASCADE,
+  alias_key VARCHAR(191) NOT NULL,
+  PRIMARY KEY (country_id, alias_key)
+);
+CREATE INDEX idx_country_aliases_alias_key ON country_aliases (alias_key);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- Every rate of every refresh; `currencies.exchange_rate` only keeps the latest one.
+CREATE TABLE exchange_rates (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  currency_code VARCHAR(32) NOT NULL,
+  rate DOUBLE NOT NULL,
+  rate_base VARCHAR(32) NULL,
+  fetched_at TIMESTAMP NOT NULL
+);
+CREATE INDEX idx_exchange_rates_currency_fetched_at ON exchange_rates (currency_code, fetched_at);
+
+-- History starts from the rates already stored.
+INSERT INTO exchange_rates (currency_code, rate, rate_base, fetched_at)
+SELECT code, exchange_rate, rate_base, rate_updated_at FROM currencies
+WHERE exchange_rate IS NOT NULL AND rate_updated_at IS NOT NULL;
@@ -1,5 +1,5 @@
-use diesel::mysql::MysqlConnection;
-
+use std::str::FromStr;
+use cron::Schedule;
 use crate::config::db::DbPool;
 
 #[derive(Debug, Clone)]
@@ -8,6 +8,9 @@ pub struct Config {
     pub port: String,
     pub countries_api_url: String,
     pub exchange_rate_api_url: String,
+    pub refresh_interval_secs: Option<u64>,
+    pub refresh_cron: Option<String>,
+    pub run_migrations: bool,
     pub db: DbPool,
 }
 
@@ -17,6 +20,12 @@ impl Config {
         let url = std::env::var("URL").expect("URL must be set");
         let countries_api_url = std::env::var("COUNTRIES_API_URL").expect("COUNTRIES_API_URL must be set");
         let exchange_rate_api_url = std::env::var("EXCHANGE_RATE_API_URL").expect("EXCHANGE_RATE_API_URL must be set");
+        let refresh_interval_secs = std::env::var("REFRESH_INTERVAL_SECS").ok().map(|v| v.parse::<u64>().expect("REFRESH_INTERVAL_SECS must be u64 type"));
+        let refresh_cron = std::env::var("REFRESH_CRON").ok();
+        if let Some(expression) = &refresh_cron {
+            validate_refresh_cron(expression).unwrap_or_else(|e| panic!("{}", e));
+        }
+        let run_migrations = std::env::var("RUN_MIGRATIONS").map(|v| v == "true" || v == "1").unwrap_or(false);
         let db_url = DbPool::new();
 
         Config {
@@ -24,10 +33,48 @@ impl Config {
             url,
             countries_api_url,
             exchange_rate_api_url,
+            refresh_interval_secs,
+            refresh_cron,
+            run_migrations,
             db: db_url,
         }
     }
 }
 
+/// Checks that `expression` parses as a `cron` schedule, which takes a leading seconds field:
+/// `sec min hour day-of-month month day-of-week [year]`.
+pub fn validate_refresh_cron(expression: &str) -> Result<(), String> {
+    match Schedule::from_str(expression) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let fields = expression.split_whitespace().count();
+            if fields == 6 || fields == 7 {
+                Err(format!("REFRESH_CRON is not a valid cron expression ({}): {}", expression, e))
+            } else {
+                Err(format!(
+                    "REFRESH_CRON must have 6 or 7 fields (sec min hour day-of-month month day-of-week [year]), got {} in '{}'; for example '0 0 * * * *' runs hourly",
+                    fields, expression,
+                ))
+            }
+        }
+    }
+}
+
 unsafe impl Send for Config {}
 unsafe impl Sync for Config {}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_refresh_cron;
+
+    #[test]
+    fn five_field_cron_is_refused_with_the_expected_format() {
+        let error = validate_refresh_cron("0 * * * *").unwrap_err();
+        assert!(error.contains("must have 6 or 7 fields"), "{}", error);
+        assert!(error.contains("got 5"), "{}", error);
+
+        assert!(validate_refresh_cron("0 0 * * * *").is_ok());
+        assert!(validate_refresh_cron("0 0 * * * * 2030").is_ok());
+        assert!(validate_refresh_cron("0 0 25 * * *").unwrap_err().contains("not a valid cron expression"));
+    }
+}
@@ -1,8 +1,24 @@
-use crate::routes::{countries_data::{delete_country_data, get_countries_and_last_refreash, get_countries_data, get_country_by_name, get_summary_image, refresh_countries_data}, healthz::check_health};
+use crate::routes::{api_error::ApiError, countries_data::{delete_country_data, get_countries_and_last_refreash, get_countries_data, get_country_by_id, get_country_by_name, get_summary_image, refresh_countries_data, suggest_countries}, currencies::{convert_currency, get_currencies, get_currency_by_code, get_currency_history}, healthz::check_health, jobs::get_job_status};
 // use crate::routes::me::me;
-use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse, web};
 
 pub fn config(conf: &mut web::ServiceConfig) {
-    let scope = web::scope("/api/v1").service(check_health).service(get_summary_image).service(refresh_countries_data).service(get_countries_data).service(get_country_by_name).service(delete_country_data).service(get_countries_and_last_refreash);
+    // Malformed query strings and path segments use the same error envelope as the handlers.
+    let query_config = web::QueryConfig::default().error_handler(|err, _req| {
+        ApiError::Validation(serde_json::json!({ "query": err.to_string() })).into()
+    });
+    let path_config = web::PathConfig::default().error_handler(|err, _req| {
+        ApiError::Validation(serde_json::json!({ "path": err.to_string() })).into()
+    });
+
+    let scope = web::scope("/api/v1").app_data(query_config).app_data(path_config).service(check_health).service(get_summary_image).service(refresh_countries_data).service(get_countries_data).service(suggest_countries).service(get_country_by_name).service(get_country_by_id).service(delete_country_data).service(get_countries_and_last_refreash).service(get_job_status).service(get_currencies).service(get_currency_by_code).service(get_currency_history).service(convert_currency);
     conf.service(scope);
+    conf.default_service(web::to(route_not_found));
+}
+
+async fn route_not_found(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    Err(ApiError::NotFound(
+        "Route not found".to_string(),
+        format!("No route for {} {}", req.method(), req.path()),
+    ))
 }
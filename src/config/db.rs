@@ -1,89 +1,115 @@
-use diesel::mysql::MysqlConnection;
-use diesel::prelude::*;
-use diesel::SelectableHelper;
-use crate::models::countries_models::Country;
-use crate::models::models::CacheMetadata;
-use crate::schema::cache_metadata::top_countries_json;
-use crate::schema::countries::dsl::*;
-use crate::schema::cache_metadata::dsl::*;
-use crate::models::models::Country as CountryModel;
-
-#[derive(Debug, Clone)]
-pub struct DbPool {
-    pub db_url: String,
+use std::ops::Deref;
+use std::sync::Arc;
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+use std::time::Duration;
+use actix_web::web;
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+use diesel::r2d2::{Builder, ManageConnection};
+use diesel::r2d2::PoolError;
+use crate::repository::{self, CountryRepository};
+
+#[derive(Debug)]
+pub enum DbError {
+    Pool(PoolError),
+    Connection(diesel::ConnectionError),
+    Query(diesel::result::Error),
+    Blocking(String),
+    // Only the SQL backends run migrations.
+    #[cfg_attr(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")), allow(dead_code))]
+    Migration(String),
 }
 
-pub fn establish_connection(database_url: String) -> MysqlConnection {
-    MysqlConnection::establish(&database_url)
-        .unwrap_or_else(|e| panic!("Error connecting to {}: {e}", database_url))
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::Pool(e) => write!(f, "Could not get a database connection: {}", e),
+            DbError::Connection(e) => write!(f, "Could not open a database connection: {}", e),
+            DbError::Query(e) => write!(f, "{}", e),
+            DbError::Blocking(e) => write!(f, "Database task failed to complete: {}", e),
+            DbError::Migration(e) => write!(f, "Migration error: {}", e),
+        }
+    }
 }
 
-impl DbPool {
-    pub fn new() -> Self {
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env");
-        DbPool { db_url: database_url }
+impl From<PoolError> for DbError {
+    fn from(e: PoolError) -> Self {
+        DbError::Pool(e)
     }
+}
 
-    pub fn insert_or_update_country(&self, country: Country) -> QueryResult<usize> {
+impl From<diesel::ConnectionError> for DbError {
+    fn from(e: diesel::ConnectionError) -> Self {
+        DbError::Connection(e)
+    }
+}
 
-       let mut conn =  establish_connection(self.db_url.clone());
+impl From<diesel::result::Error> for DbError {
+    fn from(e: diesel::result::Error) -> Self {
+        DbError::Query(e)
+    }
+}
 
-        let exists = countries
-            .filter(name.eq(&country.name))
-            .limit(1)
-            .select(CountryModel::as_select())
-            .load(&mut conn)
-            .expect("Error loading country");
+pub type DbResult<T> = Result<T, DbError>;
 
-        let upsert_data = country.struct_to_upsert_country();
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+fn env_u64(key: &str) -> Option<u64> {
+    std::env::var(key).ok().map(|v| v.parse::<u64>().unwrap_or_else(|_| panic!("{} must be u64 type", key)))
+}
 
-        println!("Upserting country: {:?}", upsert_data);
+/// r2d2 pool settings shared by every backend, read from the optional `DB_POOL_*` variables.
+/// Callers build with `build_unchecked`, so the server still starts while the database is
+/// unreachable and handlers get a `DbError::Pool` instead.
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub fn pool_builder<M: ManageConnection>() -> Builder<M> {
+    let max_size = env_u64("DB_POOL_MAX_SIZE").unwrap_or(10) as u32;
+    let min_idle = env_u64("DB_POOL_MIN_IDLE").map(|v| v as u32);
+    let connection_timeout = env_u64("DB_POOL_CONNECTION_TIMEOUT_SECS").unwrap_or(5);
+    let idle_timeout = env_u64("DB_POOL_IDLE_TIMEOUT_SECS").unwrap_or(600);
+    let max_lifetime = env_u64("DB_POOL_MAX_LIFETIME_SECS").unwrap_or(1800);
+
+    diesel::r2d2::Pool::builder()
+        .max_size(max_size)
+        .min_idle(min_idle)
+        .connection_timeout(Duration::from_secs(connection_timeout))
+        .idle_timeout(Some(Duration::from_secs(idle_timeout)))
+        .max_lifetime(Some(Duration::from_secs(max_lifetime)))
+        .test_on_check_out(true)
+}
 
-        if !exists.is_empty() {
-            diesel::update(countries.filter(name.eq(&country.name)))
-                .set(&upsert_data)
-                .execute(&mut conn)
-        } else {
-            diesel::insert_into(countries)
-                .values(&upsert_data)
-                .execute(&mut conn)
-        }
-    }
+/// Handle to the configured storage backend. Derefs to [`CountryRepository`].
+#[derive(Debug, Clone)]
+pub struct DbPool {
+    repo: Arc<dyn CountryRepository>,
+}
 
-    pub fn get_all_countries(&self) -> QueryResult<Vec<CountryModel>> {
-        let mut conn = establish_connection(self.db_url.clone());
-        countries.load::<CountryModel>(&mut conn)
+impl DbPool {
+    pub fn new() -> Self {
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env");
+        DbPool::connect(&database_url)
     }
 
-    pub fn get_country_by_name(&self, country_name: &str) -> QueryResult<Option<CountryModel>> {
-        let mut conn = establish_connection(self.db_url.clone());
-        countries
-            .filter(name.eq(country_name))
-            .first::<CountryModel>(&mut conn)
-            .optional()
+    pub fn connect(database_url: &str) -> Self {
+        DbPool { repo: repository::connect(database_url) }
     }
 
-    pub fn delete_country_by_name(&self, country_name: &str) -> QueryResult<usize> {
-        let mut conn = establish_connection(self.db_url.clone());
-        diesel::delete(countries.filter(name.eq(country_name))).execute(&mut conn)
+    /// Runs a blocking repository call on actix's blocking thread pool so async handlers
+    /// never wait on the database from a worker thread.
+    pub async fn run<T, F>(&self, f: F) -> DbResult<T>
+    where
+        F: FnOnce(&DbPool) -> DbResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        web::block(move || f(&db))
+            .await
+            .map_err(|e| DbError::Blocking(e.to_string()))?
     }
+}
 
-    pub fn save_summary_metadata(&self, top_countries: Vec<CountryModel>, last_refreshed_at_: Option<String>) -> QueryResult<usize> {
-        let mut conn = establish_connection(self.db_url.clone());
-
-        let top_json = serde_json::json!(top_countries).to_string();
-
-        let new_metadata = CacheMetadata {
-            file_path: "cache/summary.png".to_string(),
-            total_countries: top_countries.len() as i32,
-            top_countries_json: top_json,
-            last_refreshed_at: last_refreshed_at_.as_ref().and_then(|ts| {
-            chrono::NaiveDateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S%.fZ").ok()
-        }).expect("Invalid timestamp format"),
-        };
+impl Deref for DbPool {
+    type Target = dyn CountryRepository;
 
-        diesel::insert_into(cache_metadata)
-            .values(&new_metadata)
-            .execute(&mut conn)
+    fn deref(&self) -> &Self::Target {
+        self.repo.as_ref()
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod config;
 pub mod config_scope;
 pub mod db;
@@ -0,0 +1 @@
+pub mod refresh_jobs;
\ No newline at end of file
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use crate::config::db::DbPool;
+use crate::repository::RefreshLock;
+use crate::models::models::RefreshRun;
+
+// Finished jobs beyond this count are evicted oldest-first; their outcome stays in `refresh_runs`.
+const MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPhase {
+    Queued,
+    Fetching,
+    Upserting,
+    Rendering,
+    Running, // in progress on another instance; only the database record is visible here
+    Completed,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RefreshJob {
+    pub id: String,
+    pub triggered_by: String,
+    pub phase: JobPhase,
+    pub countries_processed: usize,
+    pub errors: Vec<String>,
+    pub result: Option<serde_json::Value>,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub enum BeginRefreshError {
+    AlreadyRunning(Option<String>),
+    Database(String),
+}
+
+/// In-process registry of refresh jobs, shared between the HTTP handlers and the scheduler.
+#[derive(Debug, Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, RefreshJob>>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        JobRegistry::default()
+    }
+
+    /// Starts a refresh job if none is running in this process or on any other instance.
+    /// The returned lock must be held until the job finishes.
+    pub async fn begin(&self, db: &DbPool, triggered_by: &str) -> Result<(JobHandle, RefreshLock), BeginRefreshError> {
+        let job = self.try_create(triggered_by).map_err(|running_id| BeginRefreshError::AlreadyRunning(Some(running_id)))?;
+
+        let lock = match db.run(|db| db.try_acquire_refresh_lock()).await {
+            Ok(Some(lock)) => lock,
+            Ok(None) => {
+                self.discard(&job.id);
+                let running_id = db.run(|db| db.get_running_refresh_run()).await.ok().flatten().map(|run| run.job_id);
+                return Err(BeginRefreshError::AlreadyRunning(running_id));
+            },
+            Err(e) => {
+                self.discard(&job.id);
+                return Err(BeginRefreshError::Database(e.to_string()));
+            }
+        };
+
+        let run = RefreshRun {
+            job_id: job.id.clone(),
+            triggered_by: triggered_by.to_string(),
+            status: "running".to_string(),
+            countries_processed: 0,
+            error: None,
+            started_at: chrono::Utc::now().naive_utc(),
+            finished_at: None,
+        };
+        if let Err(e) = db.run(move |db| db.save_refresh_run(run)).await {
+            self.discard(&job.id);
+            return Err(BeginRefreshError::Database(e.to_string()));
+        }
+
+        Ok((job, lock))
+    }
+
+    fn try_create(&self, triggered_by: &str) -> Result<JobHandle, String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(running) = jobs.values().find(|job| job.finished_at.is_none()) {
+            return Err(running.id.clone());
+        }
+
+        let job = RefreshJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            triggered_by: triggered_by.to_string(),
+            phase: JobPhase::Queued,
+            countries_processed: 0,
+            errors: Vec::new(),
+            result: None,
+            started_at: chrono::Utc::now().naive_utc(),
+            finished_at: None,
+        };
+        let id = job.id.clone();
+        Self::evict_finished(&mut jobs);
+        jobs.insert(id.clone(), job);
+
+        Ok(JobHandle { id, registry: self.clone() })
+    }
+
+    fn discard(&self, job_id: &str) {
+        self.jobs.lock().unwrap().remove(job_id);
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<RefreshJob> {
+        self.jobs.lock().unwrap().get(job_id).cloned()
+    }
+
+    fn evict_finished(jobs: &mut HashMap<String, RefreshJob>) {
+        let mut finished: Vec<(String, NaiveDateTime)> = jobs.values()
+            .filter_map(|job| job.finished_at.map(|at| (job.id.clone(), at)))
+            .collect();
+        if finished.len() < MAX_FINISHED_JOBS {
+            return;
+        }
+        finished.sort_by_key(|(_, at)| *at);
+        for (job_id, _) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_JOBS) {
+            jobs.remove(job_id);
+        }
+    }
+
+    fn update(&self, job_id: &str, f: impl FnOnce(&mut RefreshJob)) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(job_id) {
+            f(job);
+        }
+    }
+}
+
+/// Progress reporter handed to the refresh pipeline for a single job.
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    pub id: String,
+    registry: JobRegistry,
+}
+
+impl JobHandle {
+    pub fn set_phase(&self, phase: JobPhase) {
+        self.registry.update(&self.id, |job| job.phase = phase);
+    }
+
+    pub fn set_countries_processed(&self, count: usize) {
+        self.registry.update(&self.id, |job| job.countries_processed = count);
+    }
+
+    pub fn push_error(&self, error: String) {
+        self.registry.update(&self.id, |job| job.errors.push(error));
+    }
+
+    pub fn complete(&self, result: serde_json::Value) {
+        self.registry.update(&self.id, |job| {
+            job.phase = JobPhase::Completed;
+            job.result = Some(result);
+            job.finished_at = Some(chrono::Utc::now().naive_utc());
+        });
+    }
+
+    pub fn fail(&self, error: String) {
+        self.registry.update(&self.id, |job| {
+            job.phase = JobPhase::Failed;
+            job.errors.push(error);
+            job.finished_at = Some(chrono::Utc::now().naive_utc());
+        });
+    }
+
+    pub fn snapshot(&self) -> Option<RefreshJob> {
+        self.registry.get(&self.id)
+    }
+}
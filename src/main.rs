@@ -1,14 +1,52 @@
 mod config;
 mod routes;
 mod models;
+mod repository;
 mod schema;
+mod scheduler;
+mod jobs;
+mod search;
 use actix_cors::Cors;
-use actix_web::{App, HttpServer, http::header, middleware::Logger, web};
-use config::{config::Config, config_scope};
+use actix_web::{App, HttpServer, http::header, middleware::{self, Logger}, web};
+use config::{config::Config, config_scope, db::{DbError, DbPool}};
 use dotenv::dotenv;
+use jobs::refresh_jobs::JobRegistry;
+use search::suggest_index::SuggestIndex;
 
 pub struct AppState {
     env: Config,
+    jobs: JobRegistry,
+    suggest: SuggestIndex,
+}
+
+fn apply_migrations(db: &DbPool) -> std::io::Result<()> {
+    println!("Running pending migrations......");
+    match db.run_pending_migrations() {
+        Ok(applied) if applied.is_empty() => println!("Database schema is up to date"),
+        Ok(applied) => println!("Applied migrations: {}", applied.join(", ")),
+        Err(e) => {
+            println!("Error running migrations: {}", e);
+            return Err(std::io::Error::other(e.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Closes refresh runs left as `running` by a process that stopped mid-refresh.
+/// Skipped while another instance holds the refresh lock, since its run is still live.
+fn fail_interrupted_refresh_runs(db: &DbPool) {
+    match db.try_acquire_refresh_lock() {
+        Ok(Some(lock)) => {
+            match db.fail_stale_refresh_runs("Interrupted before the refresh finished") {
+                Ok(0) => {},
+                Ok(count) => println!("Marked {} interrupted refresh runs as failed", count),
+                Err(e) => println!("Error closing interrupted refresh runs: {}", e),
+            }
+            drop(lock);
+        },
+        Ok(None) => println!("Another instance is refreshing, leaving its refresh run open"),
+        Err(e) => println!("Error closing interrupted refresh runs: {}", e),
+    }
 }
 
 #[actix_web::main]
@@ -22,16 +60,49 @@ async fn main() -> std::io::Result<()> {
             std::env::set_var("RUST_LOG", "actix_web=info");
         }
     }
+
+    // `--migrate-only` only needs DATABASE_URL, so deploy pipelines can run it before the app config exists.
+    if std::env::args().any(|arg| arg == "--migrate-only") {
+        return apply_migrations(&DbPool::new());
+    }
+
     println!("Starting Server......");
     let config = Config::init();
 
+    if config.run_migrations {
+        apply_migrations(&config.db)?;
+    }
+
+    match config.db.check_schema_version() {
+        Ok(_) => {},
+        Err(DbError::Migration(e)) => {
+            println!("Refusing to start: {}", e);
+            return Err(std::io::Error::other(e));
+        },
+        Err(e) => println!("Could not verify database schema version: {}", e),
+    }
+
     let port: u16 = config.port.parse().expect("PORT must be i16 type");
     let url: String = config.url.clone().parse().expect("URL must be String type");
 
+    fail_interrupted_refresh_runs(&config.db);
+
+    let jobs = JobRegistry::new();
+
+    let suggest = SuggestIndex::new();
+    match suggest.reload(&config.db).await {
+        Ok(count) => println!("Suggest index loaded with {} countries", count),
+        Err(e) => println!("Error loading suggest index, starting empty until the next refresh: {}", e),
+    }
+
     let app_state = web::Data::new(AppState {
         env: config.clone(),
+        jobs: jobs.clone(),
+        suggest: suggest.clone(),
     });
 
+    scheduler::refresh_scheduler::start(config.clone(), jobs, suggest);
+
     println!("Server Started and running on {}:{}......", url, port);
     
     HttpServer::new(move || {
@@ -48,6 +119,7 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(app_state.clone())
             .configure(config_scope::config)
+            .wrap(middleware::from_fn(routes::api_error::negotiate_problem_json))
             .wrap(cors)
             .wrap(Logger::default())
     })
@@ -1,4 +1,4 @@
-use crate::{models::models::UpsertCountry, schema::countries::currency_code};
+use crate::models::{country_names::{alias_keys, name_key}, models::UpsertCountry};
 
 #[derive(Debug, Clone)]
 pub struct Country {
@@ -6,12 +6,16 @@ pub struct Country {
     pub capital: Option<String>,
     pub region: Option<String>,
     pub population: Option<u64>,
-    pub currencies: Option<Currency>,
-    pub independent: Option<bool>,
+    /// Every currency the country uses, in upstream order; the first is its primary currency.
+    pub currencies: Vec<Currency>,
     pub exchange_rate: Option<f64>,
     pub estimated_gdp: Option<f64>,
     pub flag_url: Option<String>,
     pub last_refreshed_at: Option<String>,
+    pub alpha2_code: Option<String>,
+    pub alpha3_code: Option<String>,
+    pub numeric_code: Option<String>,
+    pub alt_spellings: Vec<String>,
 }
 
 
@@ -20,52 +24,36 @@ pub struct Currency {
     pub code: Option<String>,
     pub name: Option<String>,
     pub symbol: Option<String>,
+    pub exchange_rate: Option<f64>,
 }
 
 
 impl Country {
-    pub fn new(
-        name: String,
-        capital: String,
-        region: String,
-        population: u64,
-        flag_url: String,
-        currencies: Currency,
-        independent: Option<bool>
-    ) -> Self {
-        Country {
-            name: Some(name),
-            capital: Some(capital),
-            region: Some(region),
-            population: Some(population),
-            flag_url: Some(flag_url),
-            currencies: Some(currencies),
-            independent,
-            last_refreshed_at: None,
-            exchange_rate: None,
-            estimated_gdp: None,
-        }
-    }
-
     pub fn new_from_json(json: &serde_json::Value) -> Self {
         let name = json.get("name").and_then(|v| v.as_str()).map(|s| s.to_string());
         let capital = json.get("capital").and_then(|v| v.as_str()).map(|s| s.to_string());
         let region = json.get("region").and_then(|v| v.as_str()).map(|s| s.to_string());
         let population = json.get("population").and_then(|v| v.as_u64());
         let flag_url = json.get("flag").and_then(|v| v.as_str()).map(|s| s.to_string());
+        let alpha2_code = json.get("alpha2Code").and_then(|v| v.as_str()).map(|s| s.to_uppercase());
+        let alpha3_code = json.get("alpha3Code").and_then(|v| v.as_str()).map(|s| s.to_uppercase());
+        let numeric_code = json.get("numericCode").and_then(|v| v.as_str()).map(|s| s.to_string());
+        let alt_spellings = json
+            .get("altSpellings")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+            .unwrap_or_default();
 
-        // let currencies_json = json.get("currencies")?.as_array()?;
         let currencies = json
             .get("currencies")
             .and_then(|v| v.as_array())
-            .and_then(|arr| arr.get(0))
-            .map(|c| Currency {
+            .map(|arr| arr.iter().map(|c| Currency {
                 code: c.get("code").and_then(|v| v.as_str()).map(|s| s.to_string()),
                 name: c.get("name").and_then(|v| v.as_str()).map(|s| s.to_string()),
                 symbol: c.get("symbol").and_then(|v| v.as_str()).map(|s| s.to_string()),
-            });
-
-        let independent = json.get("independent").and_then(|v| v.as_bool());
+                exchange_rate: None,
+            }).collect())
+            .unwrap_or_default();
 
        Country {
             name,
@@ -73,11 +61,14 @@ impl Country {
             region,
             population,
             flag_url,
-            currencies: currencies,
-            independent,
+            currencies,
             last_refreshed_at: None,
             exchange_rate: None,
             estimated_gdp: None,
+            alpha2_code,
+            alpha3_code,
+            numeric_code,
+            alt_spellings,
         }
     }
 
@@ -94,39 +85,36 @@ impl Country {
     }
 
     pub fn get_currency_code(&self) -> Option<&str> {
-        let country = self.clone();
-        if country.currencies.is_none() {
-            return None;
-        } else {
-            if country.clone().currencies.unwrap().code.is_none() {
-                return None;
-            } else {
-                return self.currencies.as_ref()?.code.as_deref()
-            }
-        }
+        self.currencies.first()?.code.as_deref()
     }
 
     pub fn get_currency_code_owned(&self) -> Option<String> {
-        let country = self.clone();
-        if country.currencies.is_none() {
-            return None;
-        } else {
-            if country.clone().currencies.unwrap().code.is_none() {
-                return None;
-            } else {
-                return self.currencies.as_ref()?.code.clone()
-            }
-        }
+        self.currencies.first()?.code.clone()
+    }
+
+    /// Folded keys of the upstream `altSpellings` that lookups also resolve to this country.
+    pub fn alias_keys(&self) -> Vec<String> {
+        alias_keys(self.name.as_deref(), &self.alt_spellings)
     }
 
-    pub fn structure_country_for_return(&self, index: usize) -> serde_json::Value {
+    /// Response shape of a stored country; `id` is its `countries.id` primary key.
+    pub fn structure_country_for_return(&self, id: i32) -> serde_json::Value {
         serde_json::json!({
-            "id": index,
+            "id": id,
             "name": self.name,
+            "alpha2_code": self.alpha2_code,
+            "alpha3_code": self.alpha3_code,
+            "numeric_code": self.numeric_code,
             "capital": self.capital,
             "region": self.region,
             "population": self.population,
-            "currency_code": self.currencies.as_ref().and_then(|c| c.code.clone()),
+            "currency_code": self.get_currency_code_owned(),
+            "currencies": self.currencies.iter().map(|c| serde_json::json!({
+                "code": c.code,
+                "name": c.name,
+                "symbol": c.symbol,
+                "exchange_rate": c.exchange_rate,
+            })).collect::<Vec<_>>(),
             "exchange_rate": self.exchange_rate,
             "estimated_gdp": self.estimated_gdp,
             "flag_url": self.flag_url,
@@ -134,7 +122,7 @@ impl Country {
         })
     }
 
-pub fn struct_to_upsert_country(&self) -> UpsertCountry {
+pub fn struct_to_upsert_country(&self) -> UpsertCountry<'_> {
     let population = self.population.map(|p| p as i64);
 
     UpsertCountry {
@@ -149,6 +137,10 @@ pub fn struct_to_upsert_country(&self) -> UpsertCountry {
         last_refreshed_at: self.last_refreshed_at.as_ref().and_then(|ts| {
             chrono::NaiveDateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S%.fZ").ok()
         }),
+        alpha2_code: self.alpha2_code.as_deref(),
+        alpha3_code: self.alpha3_code.as_deref(),
+        numeric_code: self.numeric_code.as_deref(),
+        name_key: self.name.as_deref().map(name_key),
     }
 }
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+/// How many "did you mean" suggestions a failed lookup returns at most.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Folds a country name to the key it is matched by: transliterated to ASCII, lower-cased, and
+/// stripped of everything but letters and digits, so "Côte d'Ivoire", "cote divoire" and
+/// "COTE-D'IVOIRE" all become `cotedivoire`.
+pub fn name_key(name: &str) -> String {
+    deunicode::deunicode(name)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Keys of a country's alternate spellings, without duplicates or the key of its own name.
+pub fn alias_keys(name: Option<&str>, alt_spellings: &[String]) -> Vec<String> {
+    let own_key = name.map(name_key);
+    let mut keys: Vec<String> = Vec::new();
+    for key in alt_spellings.iter().map(|s| name_key(s)) {
+        if !key.is_empty() && Some(&key) != own_key.as_ref() && !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+/// Ranks `names` by edit distance between their keys and the key of `wanted`, counting a swap of two
+/// adjacent letters as one edit, and keeps the closest few within a third of the key's length
+/// (or 2 edits for short keys).
+pub fn suggest_names(wanted: &str, names: Vec<String>) -> Vec<String> {
+    let wanted_key = name_key(wanted);
+    let max_distance = (wanted_key.chars().count() / 3).max(2);
+
+    let mut ranked: Vec<(usize, String)> = names
+        .into_iter()
+        .map(|name| (strsim::osa_distance(&wanted_key, &name_key(&name)), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    ranked.sort();
+    ranked.into_iter().take(MAX_SUGGESTIONS).map(|(_, name)| name).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_key_ignores_case_accents_and_punctuation() {
+        assert_eq!(name_key("Côte d'Ivoire"), "cotedivoire");
+        assert_eq!(name_key("COTE-D'IVOIRE"), "cotedivoire");
+        assert_eq!(name_key("  São Tomé and Príncipe "), "saotomeandprincipe");
+        assert_eq!(name_key("''"), "");
+    }
+
+    #[test]
+    fn alias_keys_drop_duplicates_and_the_own_name() {
+        let spellings = ["CI", "Ivory Coast", "ivory-coast", "Côte d'Ivoire", "!"].map(String::from);
+        assert_eq!(alias_keys(Some("Côte d'Ivoire"), &spellings), ["ci", "ivorycoast"]);
+    }
+
+    #[test]
+    fn suggestions_are_ranked_by_distance_within_the_limit() {
+        let names = ["Niger", "Nigeria", "Algeria", "Mali", "Nicaragua"].map(String::from).to_vec();
+        assert_eq!(suggest_names("Nigerai", names.clone()), ["Nigeria", "Niger"]);
+        assert_eq!(suggest_names("nigr", names.clone()), ["Niger"]);
+        assert!(suggest_names("Atlantis", names).is_empty());
+
+        let close = ["Malta", "Mali", "Male", "Mala"].map(String::from).to_vec();
+        assert_eq!(suggest_names("Mal", close), ["Mala", "Male", "Mali"]);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod countries_models;
+pub mod country_names;
+#[allow(clippy::module_inception)]
 pub mod models;
\ No newline at end of file
@@ -2,15 +2,16 @@ use chrono::NaiveDateTime;
 use diesel::{AsChangeset, Insertable, Queryable, Selectable};
 use serde::{Deserialize, Serialize};
 use crate::schema::countries;
-use diesel::prelude::*;
+use crate::schema::country_aliases;
+use crate::schema::{country_currencies, currencies, exchange_rates};
 use crate::schema::cache_metadata;
+use crate::schema::refresh_runs;
 
 // ─────────────────────────────
 //  Queryable + Selectable struct
 // ─────────────────────────────
-#[derive(Debug, Queryable, Selectable, Serialize, Deserialize)]
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
 #[diesel(table_name = countries)]
-#[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct Country {
     pub id: i32,
     pub name: Option<String>,
@@ -22,12 +23,16 @@ pub struct Country {
     pub estimated_gdp: Option<f64>,
     pub flag_url: Option<String>,
     pub last_refreshed_at: Option<NaiveDateTime>,
+    pub alpha2_code: Option<String>,
+    pub alpha3_code: Option<String>,
+    pub numeric_code: Option<String>,
+    pub name_key: Option<String>,
 }
 
 // ─────────────────────────────
 //  Insertable + AsChangeset struct
 // ─────────────────────────────
-#[derive(Insertable, AsChangeset, Debug, Deserialize)]
+#[derive(Insertable, AsChangeset, Debug, Serialize, Deserialize)]
 #[diesel(table_name = countries)]
 pub struct UpsertCountry<'a> {
     pub name: Option<&'a str>,
@@ -39,15 +44,102 @@ pub struct UpsertCountry<'a> {
     pub estimated_gdp: Option<f64>,
     pub flag_url: Option<&'a str>,
     pub last_refreshed_at: Option<NaiveDateTime>,
+    pub alpha2_code: Option<&'a str>,
+    pub alpha3_code: Option<&'a str>,
+    pub numeric_code: Option<&'a str>,
+    pub name_key: Option<String>,
 }
 
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = currencies)]
+pub struct Currency {
+    pub code: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub exchange_rate: Option<f64>,
+    pub rate_updated_at: Option<NaiveDateTime>,
+    /// The currency `exchange_rate` is quoted against, as reported by the rate API.
+    pub rate_base: Option<String>,
+}
 
-#[derive(Insertable, Queryable, Serialize, Debug)]
+/// One rate of one refresh, kept in `exchange_rates` after `currencies` has moved on.
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = exchange_rates)]
+pub struct NewExchangeRate {
+    pub currency_code: String,
+    pub rate: f64,
+    pub rate_base: Option<String>,
+    pub fetched_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = exchange_rates)]
+pub struct ExchangeRate {
+    pub id: i32,
+    pub currency_code: String,
+    pub rate: f64,
+    pub rate_base: Option<String>,
+    pub fetched_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = country_currencies)]
+pub struct CountryCurrency {
+    pub country_id: i32,
+    pub currency_code: String,
+    pub position: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = country_aliases)]
+pub struct CountryAlias {
+    pub country_id: i32,
+    pub alias_key: String,
+}
+
+
+#[derive(Insertable, Queryable, Serialize, Debug, Clone)]
 #[diesel(table_name = cache_metadata)]
-#[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct CacheMetadata {
     pub file_path: String,
     pub total_countries: i32,
     pub top_countries_json: String, // serialized JSON array
     pub last_refreshed_at: NaiveDateTime,
+}
+
+
+#[derive(Insertable, Queryable, Serialize, Debug)]
+#[diesel(table_name = refresh_runs)]
+pub struct RefreshRun {
+    pub job_id: String,
+    pub triggered_by: String,
+    pub status: String, // "running", "success" or "failed"
+    pub countries_processed: i32,
+    pub error: Option<String>,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = refresh_runs)]
+pub struct RefreshRunRecord {
+    pub id: i32,
+    pub job_id: String,
+    pub triggered_by: String,
+    pub status: String,
+    pub countries_processed: i32,
+    pub error: Option<String>,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl CacheMetadata {
+    pub fn new(total_countries: usize, top_countries: &[UpsertCountry<'_>], last_refreshed_at: NaiveDateTime) -> Self {
+        CacheMetadata {
+            file_path: "cache/summary.png".to_string(),
+            total_countries: total_countries as i32,
+            top_countries_json: serde_json::json!(top_countries).to_string(),
+            last_refreshed_at,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,741 @@
+//! The `filter=` expression language of `GET /countries`, e.g.
+//! `region in (Africa,Asia) and population > 1e7 and currency_code != USD`.
+//!
+//! ```text
+//! expr       := and_expr ("or" and_expr)*
+//! and_expr   := unary ("and" unary)*
+//! unary      := "not" unary | "(" expr ")" | comparison
+//! comparison := column op value
+//!             | column ["not"] "in" "(" value ("," value)* ")"
+//!             | column "is" ["not"] "null"
+//! op         := "=" | "!=" | "<>" | "<" | "<=" | ">" | ">="
+//! value      := number | word | 'quoted' | "quoted"    (a doubled quote escapes itself)
+//! ```
+//!
+//! Keywords are case-insensitive. Text columns only support equality and `in`, compared ignoring case.
+//! Comparisons against a NULL column are unknown, as in SQL, so `currency_code != USD` skips countries
+//! with no currency.
+//!
+//! `currency_code` stands for every currency a country uses, like `currency=`: `currency_code = USD`
+//! keeps countries using the dollar among others, `currency_code != USD` those with currencies but not
+//! the dollar, and `currency_code is null` those with no currency at all.
+
+use crate::models::models::Country as CountryModel;
+
+// Longest expression and deepest nesting accepted, so a hostile `filter=` can't exhaust the stack.
+const MAX_FILTER_LENGTH: usize = 1000;
+const MAX_FILTER_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterColumn {
+    Name,
+    Capital,
+    Region,
+    CurrencyCode,
+    FlagUrl,
+    Population,
+    ExchangeRate,
+    EstimatedGdp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind {
+    Text,
+    Integer,
+    Number,
+}
+
+// Filterable `countries` columns by name; `gdp` is accepted as short for `estimated_gdp`.
+const FILTER_COLUMNS: [(&str, FilterColumn); 9] = [
+    ("name", FilterColumn::Name),
+    ("capital", FilterColumn::Capital),
+    ("region", FilterColumn::Region),
+    ("currency_code", FilterColumn::CurrencyCode),
+    ("flag_url", FilterColumn::FlagUrl),
+    ("population", FilterColumn::Population),
+    ("exchange_rate", FilterColumn::ExchangeRate),
+    ("estimated_gdp", FilterColumn::EstimatedGdp),
+    ("gdp", FilterColumn::EstimatedGdp),
+];
+
+impl FilterColumn {
+    fn kind(&self) -> ColumnKind {
+        match self {
+            FilterColumn::Name | FilterColumn::Capital | FilterColumn::Region | FilterColumn::CurrencyCode | FilterColumn::FlagUrl => ColumnKind::Text,
+            FilterColumn::Population => ColumnKind::Integer,
+            FilterColumn::ExchangeRate | FilterColumn::EstimatedGdp => ColumnKind::Number,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A literal, already checked against the type of the column it is compared with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    Text(String),
+    Integer(i64),
+    Number(f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Compare { column: FilterColumn, op: CompareOp, value: FilterValue },
+    In { column: FilterColumn, values: Vec<FilterValue>, negated: bool },
+    IsNull { column: FilterColumn, negated: bool },
+}
+
+/// Why an expression was rejected. `position` is the 1-based character offset of the offending token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Quoted(String),
+    Op(CompareOp),
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    // 1-based character offset.
+    position: usize,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::Word(w) => format!("'{}'", w),
+            TokenKind::Quoted(q) => format!("'{}'", q),
+            TokenKind::Op(_) => "an operator".to_string(),
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::Comma => "','".to_string(),
+            TokenKind::End => "the end of the filter".to_string(),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+}
+
+fn error(position: usize, message: impl Into<String>) -> FilterError {
+    FilterError { position, message: message.into() }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FilterError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let kind = match c {
+            '(' => { i += 1; TokenKind::LParen },
+            ')' => { i += 1; TokenKind::RParen },
+            ',' => { i += 1; TokenKind::Comma },
+            '=' => { i += 1; TokenKind::Op(CompareOp::Eq) },
+            '!' if chars.get(i + 1) == Some(&'=') => { i += 2; TokenKind::Op(CompareOp::Ne) },
+            '<' if chars.get(i + 1) == Some(&'>') => { i += 2; TokenKind::Op(CompareOp::Ne) },
+            '<' if chars.get(i + 1) == Some(&'=') => { i += 2; TokenKind::Op(CompareOp::Le) },
+            '>' if chars.get(i + 1) == Some(&'=') => { i += 2; TokenKind::Op(CompareOp::Ge) },
+            '<' => { i += 1; TokenKind::Op(CompareOp::Lt) },
+            '>' => { i += 1; TokenKind::Op(CompareOp::Gt) },
+            '\'' | '"' => {
+                // A doubled quote inside a quoted value stands for the quote itself, as in SQL.
+                let quote = c;
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(error(position, "unterminated quoted value")),
+                        Some(ch) if *ch == quote && chars.get(i + 1) == Some(&quote) => { value.push(quote); i += 2; },
+                        Some(ch) if *ch == quote => { i += 1; break; },
+                        Some(ch) => { value.push(*ch); i += 1; },
+                    }
+                }
+                TokenKind::Quoted(value)
+            },
+            '!' => return Err(error(position, "unexpected '!', did you mean '!='?")),
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !"(),=!<>'\"".contains(chars[i]) {
+                    i += 1;
+                }
+                TokenKind::Word(chars[start..i].iter().collect())
+            }
+        };
+        tokens.push(Token { kind, position });
+    }
+
+    tokens.push(Token { kind: TokenKind::End, position: chars.len() + 1 });
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].clone();
+        if token.kind != TokenKind::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<Token, FilterError> {
+        let token = self.advance();
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            Err(error(token.position, format!("expected {} but found {}", what, token.describe())))
+        }
+    }
+
+    fn nested<T>(&mut self, position: usize, parse: impl FnOnce(&mut Self) -> Result<T, FilterError>) -> Result<T, FilterError> {
+        self.depth += 1;
+        if self.depth > MAX_FILTER_DEPTH {
+            return Err(error(position, format!("filter is nested more than {} levels deep", MAX_FILTER_DEPTH)));
+        }
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut expr = self.parse_and()?;
+        while self.peek().is_keyword("or") {
+            self.advance();
+            let rhs = self.parse_and()?;
+            expr = FilterExpr::Or(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut expr = self.parse_unary()?;
+        while self.peek().is_keyword("and") {
+            self.advance();
+            let rhs = self.parse_unary()?;
+            expr = FilterExpr::And(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, FilterError> {
+        let token = self.peek().clone();
+
+        if token.is_keyword("not") {
+            self.advance();
+            return self.nested(token.position, |p| Ok(FilterExpr::Not(Box::new(p.parse_unary()?))));
+        }
+        if token.kind == TokenKind::LParen {
+            self.advance();
+            let expr = self.nested(token.position, |p| p.parse_or())?;
+            self.expect(TokenKind::RParen, "')'")?;
+            return Ok(expr);
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_column(&mut self) -> Result<FilterColumn, FilterError> {
+        let token = self.advance();
+        let name = match &token.kind {
+            TokenKind::Word(w) => w.to_lowercase(),
+            _ => return Err(error(token.position, format!("expected a column name but found {}", token.describe()))),
+        };
+
+        FILTER_COLUMNS.iter()
+            .find(|(column_name, _)| *column_name == name)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| {
+                let expected: Vec<&str> = FILTER_COLUMNS.iter().map(|(column_name, _)| *column_name).collect();
+                error(token.position, format!("unknown column '{}', expected one of {}", name, expected.join(", ")))
+            })
+    }
+
+    fn parse_value(&mut self, column: FilterColumn) -> Result<FilterValue, FilterError> {
+        let token = self.advance();
+        let (raw, quoted) = match &token.kind {
+            TokenKind::Word(w) => (w.clone(), false),
+            TokenKind::Quoted(q) => (q.clone(), true),
+            _ => return Err(error(token.position, format!("expected a value but found {}", token.describe()))),
+        };
+
+        match column.kind() {
+            ColumnKind::Text => Ok(FilterValue::Text(raw)),
+            _ if quoted => Err(error(token.position, "numeric columns take unquoted numbers")),
+            ColumnKind::Integer => match raw.parse::<f64>() {
+                Ok(n) if n.is_finite() && n.fract() == 0.0 && n.abs() < i64::MAX as f64 => Ok(FilterValue::Integer(n as i64)),
+                Ok(_) => Err(error(token.position, format!("'{}' is not a whole number", raw))),
+                Err(_) => Err(error(token.position, format!("expected a number but found '{}'", raw))),
+            },
+            ColumnKind::Number => match raw.parse::<f64>() {
+                Ok(n) if n.is_finite() => Ok(FilterValue::Number(n)),
+                _ => Err(error(token.position, format!("expected a number but found '{}'", raw))),
+            },
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<FilterExpr, FilterError> {
+        let column = self.parse_column()?;
+        let token = self.advance();
+
+        if let TokenKind::Op(op) = token.kind {
+            if column.kind() == ColumnKind::Text && !matches!(op, CompareOp::Eq | CompareOp::Ne) {
+                return Err(error(token.position, "text columns only support =, != and in"));
+            }
+            let value = self.parse_value(column)?;
+            return Ok(FilterExpr::Compare { column, op, value });
+        }
+
+        if token.is_keyword("is") {
+            let negated = self.peek().is_keyword("not");
+            if negated {
+                self.advance();
+            }
+            let null = self.advance();
+            if !null.is_keyword("null") {
+                return Err(error(null.position, format!("expected 'null' but found {}", null.describe())));
+            }
+            return Ok(FilterExpr::IsNull { column, negated });
+        }
+
+        let negated = token.is_keyword("not");
+        let in_token = if negated { self.advance() } else { token };
+        if !in_token.is_keyword("in") {
+            return Err(error(in_token.position, format!("expected an operator, 'in' or 'is' but found {}", in_token.describe())));
+        }
+
+        self.expect(TokenKind::LParen, "'('")?;
+        let mut values = vec![self.parse_value(column)?];
+        while self.peek().kind == TokenKind::Comma {
+            self.advance();
+            values.push(self.parse_value(column)?);
+        }
+        self.expect(TokenKind::RParen, "')' or ','")?;
+
+        Ok(FilterExpr::In { column, values, negated })
+    }
+}
+
+/// Parses and type-checks a `filter=` expression.
+pub fn parse_filter(input: &str) -> Result<FilterExpr, FilterError> {
+    if input.chars().count() > MAX_FILTER_LENGTH {
+        return Err(error(MAX_FILTER_LENGTH + 1, format!("filter is longer than {} characters", MAX_FILTER_LENGTH)));
+    }
+
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, next: 0, depth: 0 };
+
+    if parser.peek().kind == TokenKind::End {
+        return Err(error(1, "filter is empty"));
+    }
+    let expr = parser.parse_or()?;
+
+    let trailing = parser.peek().clone();
+    if trailing.kind != TokenKind::End {
+        return Err(error(trailing.position, format!("expected 'and', 'or' or the end of the filter but found {}", trailing.describe())));
+    }
+    Ok(expr)
+}
+
+impl FilterExpr {
+    /// Evaluates the expression with SQL's three-valued logic: `None` is unknown, and only
+    /// `Some(true)` keeps a row. `currency_codes` are all the currencies the country uses.
+    /// Used by the in-memory backend to match the SQL ones.
+    pub fn matches(&self, country: &CountryModel, currency_codes: &[String]) -> Option<bool> {
+        match self {
+            FilterExpr::And(lhs, rhs) => match (lhs.matches(country, currency_codes), rhs.matches(country, currency_codes)) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            FilterExpr::Or(lhs, rhs) => match (lhs.matches(country, currency_codes), rhs.matches(country, currency_codes)) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            FilterExpr::Not(inner) => inner.matches(country, currency_codes).map(|m| !m),
+            FilterExpr::IsNull { column: FilterColumn::CurrencyCode, negated } => Some(currency_codes.is_empty() != *negated),
+            FilterExpr::Compare { column: FilterColumn::CurrencyCode, op, value } => {
+                let uses = uses_any(currency_codes, std::slice::from_ref(value))?;
+                Some(uses == (*op == CompareOp::Eq))
+            },
+            FilterExpr::In { column: FilterColumn::CurrencyCode, values, negated } => Some(uses_any(currency_codes, values)? != *negated),
+            FilterExpr::IsNull { column, negated } => Some(column_value(country, *column).is_none() != *negated),
+            FilterExpr::Compare { column, op, value } => {
+                let ordering = compare(&column_value(country, *column)?, value)?;
+                Some(match op {
+                    CompareOp::Eq => ordering.is_eq(),
+                    CompareOp::Ne => ordering.is_ne(),
+                    CompareOp::Lt => ordering.is_lt(),
+                    CompareOp::Le => ordering.is_le(),
+                    CompareOp::Gt => ordering.is_gt(),
+                    CompareOp::Ge => ordering.is_ge(),
+                })
+            },
+            FilterExpr::In { column, values, negated } => {
+                let stored = column_value(country, *column)?;
+                let found = values.iter().any(|v| compare(&stored, v).is_some_and(|o| o.is_eq()));
+                Some(found != *negated)
+            },
+        }
+    }
+}
+
+// Unknown for a country with no currency, as a NULL column would be.
+fn uses_any(currency_codes: &[String], wanted: &[FilterValue]) -> Option<bool> {
+    if currency_codes.is_empty() {
+        return None;
+    }
+    Some(currency_codes.iter().any(|code| {
+        let code = FilterValue::Text(code.clone());
+        wanted.iter().any(|w| compare(&code, w).is_some_and(|o| o.is_eq()))
+    }))
+}
+
+fn column_value(country: &CountryModel, column: FilterColumn) -> Option<FilterValue> {
+    match column {
+        FilterColumn::Name => country.name.clone().map(FilterValue::Text),
+        FilterColumn::Capital => country.capital.clone().map(FilterValue::Text),
+        FilterColumn::Region => country.region.clone().map(FilterValue::Text),
+        FilterColumn::CurrencyCode => country.currency_code.clone().map(FilterValue::Text),
+        FilterColumn::FlagUrl => country.flag_url.clone().map(FilterValue::Text),
+        FilterColumn::Population => country.population.map(FilterValue::Integer),
+        FilterColumn::ExchangeRate => country.exchange_rate.map(FilterValue::Number),
+        FilterColumn::EstimatedGdp => country.estimated_gdp.map(FilterValue::Number),
+    }
+}
+
+fn compare(stored: &FilterValue, wanted: &FilterValue) -> Option<std::cmp::Ordering> {
+    match (stored, wanted) {
+        (FilterValue::Text(a), FilterValue::Text(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+        (FilterValue::Integer(a), FilterValue::Integer(b)) => Some(a.cmp(b)),
+        (FilterValue::Number(a), FilterValue::Number(b)) => a.partial_cmp(b),
+        _ => None,
+    }
+}
+
+/// Defines `fn compile_filter(&FilterExpr)` for one backend, turning the AST into a boxed diesel
+/// expression over `countries`. `case_insensitive_text` wraps text comparisons in `lower()` for
+/// backends whose collation is case-sensitive. A macro because diesel can't box expressions
+/// generically over the backend.
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+macro_rules! filter_compiler {
+    ($backend:ty, $case_insensitive_text:expr) => {
+        type BoxedFilter = Box<dyn BoxableExpression<countries::table, $backend, SqlType = diesel::sql_types::Nullable<diesel::sql_types::Bool>>>;
+
+        fn compile_filter(expr: &crate::repository::filter::FilterExpr) -> BoxedFilter {
+            use crate::repository::filter::{CompareOp, FilterColumn, FilterExpr, FilterValue};
+            use crate::repository::lower;
+            use crate::schema::country_currencies;
+
+            // The parser has already matched every value to its column's type.
+            fn text(value: &FilterValue) -> String {
+                match value {
+                    FilterValue::Text(t) if $case_insensitive_text => t.to_lowercase(),
+                    FilterValue::Text(t) => t.clone(),
+                    _ => String::new(),
+                }
+            }
+            fn integer(value: &FilterValue) -> i64 {
+                match value { FilterValue::Integer(n) => *n, _ => 0 }
+            }
+            fn number(value: &FilterValue) -> f64 {
+                match value { FilterValue::Number(n) => *n, _ => 0.0 }
+            }
+            // Currency codes are stored upper-case, so this matches ignoring case on every backend.
+            fn currency(value: &FilterValue) -> String {
+                match value { FilterValue::Text(t) => t.to_uppercase(), _ => String::new() }
+            }
+
+            // `currency_code` goes through `country_currencies` like `currency=`, and is NULL for a
+            // country with no currency so that `not` leaves it out as it would a NULL column.
+            let has_currency = || countries::id.eq_any(country_currencies::table.select(country_currencies::country_id));
+            macro_rules! uses_currency {
+                ($codes:expr) => {
+                    countries::id.eq_any(
+                        country_currencies::table
+                            .filter(country_currencies::currency_code.eq_any($codes))
+                            .select(country_currencies::country_id),
+                    )
+                };
+            }
+            macro_rules! known_currency {
+                ($uses:expr) => {
+                    Box::new(diesel::dsl::case_when::<_, _, diesel::sql_types::Bool>(has_currency(), $uses)) as BoxedFilter
+                };
+            }
+
+            macro_rules! compare {
+                ($column:expr, $op:expr, $value:expr) => {
+                    match $op {
+                        CompareOp::Eq => Box::new($column.eq($value)) as BoxedFilter,
+                        CompareOp::Ne => Box::new($column.ne($value)),
+                        CompareOp::Lt => Box::new($column.lt($value)),
+                        CompareOp::Le => Box::new($column.le($value)),
+                        CompareOp::Gt => Box::new($column.gt($value)),
+                        CompareOp::Ge => Box::new($column.ge($value)),
+                    }
+                };
+            }
+            macro_rules! text_column {
+                ($column:expr, $build:ident) => {
+                    if $case_insensitive_text { $build!(lower($column)) } else { $build!($column) }
+                };
+            }
+            macro_rules! for_column {
+                ($column:expr, $text:ident, $integer:ident, $number:ident) => {
+                    match $column {
+                        FilterColumn::Name => text_column!(countries::name, $text),
+                        FilterColumn::Capital => text_column!(countries::capital, $text),
+                        FilterColumn::Region => text_column!(countries::region, $text),
+                        FilterColumn::CurrencyCode => text_column!(countries::currency_code, $text),
+                        FilterColumn::FlagUrl => text_column!(countries::flag_url, $text),
+                        FilterColumn::Population => $integer!(countries::population),
+                        FilterColumn::ExchangeRate => $number!(countries::exchange_rate),
+                        FilterColumn::EstimatedGdp => $number!(countries::estimated_gdp),
+                    }
+                };
+            }
+
+            match expr {
+                FilterExpr::And(lhs, rhs) => Box::new(compile_filter(lhs).and(compile_filter(rhs))),
+                FilterExpr::Or(lhs, rhs) => Box::new(compile_filter(lhs).or(compile_filter(rhs))),
+                FilterExpr::Not(inner) => Box::new(diesel::dsl::not(compile_filter(inner))),
+                FilterExpr::IsNull { column: FilterColumn::CurrencyCode, negated } => {
+                    if *negated { Box::new(has_currency().nullable()) } else { Box::new(diesel::dsl::not(has_currency()).nullable()) }
+                },
+                FilterExpr::Compare { column: FilterColumn::CurrencyCode, op, value } => {
+                    let codes = vec![currency(value)];
+                    if *op == CompareOp::Eq { known_currency!(uses_currency!(codes)) } else { known_currency!(diesel::dsl::not(uses_currency!(codes))) }
+                },
+                FilterExpr::In { column: FilterColumn::CurrencyCode, values, negated } => {
+                    let codes: Vec<String> = values.iter().map(currency).collect();
+                    if *negated { known_currency!(diesel::dsl::not(uses_currency!(codes))) } else { known_currency!(uses_currency!(codes)) }
+                },
+                FilterExpr::IsNull { column, negated } => {
+                    macro_rules! is_null {
+                        ($c:expr) => {
+                            if *negated { Box::new($c.is_not_null().nullable()) as BoxedFilter } else { Box::new($c.is_null().nullable()) }
+                        };
+                    }
+                    for_column!(column, is_null, is_null, is_null)
+                },
+                FilterExpr::Compare { column, op, value } => {
+                    macro_rules! text_value { ($c:expr) => { compare!($c, op, text(value)) }; }
+                    macro_rules! integer_value { ($c:expr) => { compare!($c, op, integer(value)) }; }
+                    macro_rules! number_value { ($c:expr) => { compare!($c, op, number(value)) }; }
+                    for_column!(column, text_value, integer_value, number_value)
+                },
+                FilterExpr::In { column, values, negated } => {
+                    macro_rules! within {
+                        ($c:expr, $values:expr) => {
+                            if *negated { Box::new($c.ne_all($values)) as BoxedFilter } else { Box::new($c.eq_any($values)) }
+                        };
+                    }
+                    macro_rules! text_values { ($c:expr) => { within!($c, values.iter().map(text).collect::<Vec<_>>()) }; }
+                    macro_rules! integer_values { ($c:expr) => { within!($c, values.iter().map(integer).collect::<Vec<_>>()) }; }
+                    macro_rules! number_values { ($c:expr) => { within!($c, values.iter().map(number).collect::<Vec<_>>()) }; }
+                    for_column!(column, text_values, integer_values, number_values)
+                },
+            }
+        }
+    };
+}
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub(crate) use filter_compiler;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn compare(column: FilterColumn, op: CompareOp, value: FilterValue) -> FilterExpr {
+        FilterExpr::Compare { column, op, value }
+    }
+
+    fn region_is(region: &str) -> FilterExpr {
+        compare(FilterColumn::Region, CompareOp::Eq, FilterValue::Text(region.to_string()))
+    }
+
+    fn population_over(n: i64) -> FilterExpr {
+        compare(FilterColumn::Population, CompareOp::Gt, FilterValue::Integer(n))
+    }
+
+    fn failure(input: &str) -> (usize, String) {
+        let e = parse_filter(input).unwrap_err();
+        (e.position, e.message)
+    }
+
+    fn country(fields: serde_json::Value) -> CountryModel {
+        serde_json::from_value(fields).unwrap()
+    }
+
+    fn evaluate(input: &str, country: &CountryModel, currency_codes: &[&str]) -> Option<bool> {
+        let codes: Vec<String> = currency_codes.iter().map(|c| c.to_string()).collect();
+        parse_filter(input).unwrap().matches(country, &codes)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse_filter("region = Asia or region = Africa and population > 5").unwrap(),
+            FilterExpr::Or(
+                Box::new(region_is("Asia")),
+                Box::new(FilterExpr::And(Box::new(region_is("Africa")), Box::new(population_over(5)))),
+            ),
+        );
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert_eq!(
+            parse_filter("NOT region = Asia AND population > 5").unwrap(),
+            FilterExpr::And(Box::new(FilterExpr::Not(Box::new(region_is("Asia")))), Box::new(population_over(5))),
+        );
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert_eq!(
+            parse_filter("(region = Asia or region = Africa) and population > 5").unwrap(),
+            FilterExpr::And(
+                Box::new(FilterExpr::Or(Box::new(region_is("Asia")), Box::new(region_is("Africa")))),
+                Box::new(population_over(5)),
+            ),
+        );
+    }
+
+    #[test]
+    fn values_are_typed_by_column() {
+        assert_eq!(
+            parse_filter("gdp >= 1e3 and population in (1, 2e1) and name = 'Côte d''Ivoire'").unwrap(),
+            FilterExpr::And(
+                Box::new(FilterExpr::And(
+                    Box::new(compare(FilterColumn::EstimatedGdp, CompareOp::Ge, FilterValue::Number(1000.0))),
+                    Box::new(FilterExpr::In {
+                        column: FilterColumn::Population,
+                        values: vec![FilterValue::Integer(1), FilterValue::Integer(20)],
+                        negated: false,
+                    }),
+                )),
+                Box::new(compare(FilterColumn::Name, CompareOp::Eq, FilterValue::Text("Côte d'Ivoire".to_string()))),
+            ),
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        assert_eq!(failure(""), (1, "filter is empty".to_string()));
+        assert_eq!(failure("colour = red").0, 1);
+        assert_eq!(failure("region < Asia"), (8, "text columns only support =, != and in".to_string()));
+        assert_eq!(failure("population > many"), (14, "expected a number but found 'many'".to_string()));
+        assert_eq!(failure("population = 1.5"), (14, "'1.5' is not a whole number".to_string()));
+        assert_eq!(failure("gdp > '5'"), (7, "numeric columns take unquoted numbers".to_string()));
+        assert_eq!(failure("(region = Asia"), (15, "expected ')' but found the end of the filter".to_string()));
+        assert_eq!(failure("region = 'Asia"), (10, "unterminated quoted value".to_string()));
+        assert_eq!(failure("region = Asia Africa").0, 15);
+        assert_eq!(failure("region ! Asia").0, 8);
+        assert_eq!(failure("capital is nothing"), (12, "expected 'null' but found 'nothing'".to_string()));
+        assert_eq!(failure("region in (Asia"), (16, "expected ')' or ',' but found the end of the filter".to_string()));
+    }
+
+    #[test]
+    fn rejects_overly_long_or_deep_filters() {
+        let long = format!("name = '{}'", "a".repeat(MAX_FILTER_LENGTH));
+        assert_eq!(failure(&long).0, MAX_FILTER_LENGTH + 1);
+
+        let deep = format!("{}region = Asia{}", "(".repeat(MAX_FILTER_DEPTH + 1), ")".repeat(MAX_FILTER_DEPTH + 1));
+        assert_eq!(failure(&deep), (MAX_FILTER_DEPTH + 1, format!("filter is nested more than {} levels deep", MAX_FILTER_DEPTH)));
+    }
+
+    #[test]
+    fn comparisons_with_null_are_unknown() {
+        let no_capital = country(json!({ "id": 1, "name": "Antarctica", "population": 1000 }));
+
+        assert_eq!(evaluate("capital = Oslo", &no_capital, &[]), None);
+        assert_eq!(evaluate("capital != Oslo", &no_capital, &[]), None);
+        assert_eq!(evaluate("not capital = Oslo", &no_capital, &[]), None);
+        assert_eq!(evaluate("capital not in (Oslo)", &no_capital, &[]), None);
+        assert_eq!(evaluate("capital is null", &no_capital, &[]), Some(true));
+        assert_eq!(evaluate("capital is not null", &no_capital, &[]), Some(false));
+        assert_eq!(evaluate("capital = Oslo or population > 10", &no_capital, &[]), Some(true));
+        assert_eq!(evaluate("capital = Oslo and population < 10", &no_capital, &[]), Some(false));
+        assert_eq!(evaluate("capital = Oslo and population > 10", &no_capital, &[]), None);
+    }
+
+    #[test]
+    fn text_comparisons_ignore_case() {
+        let nigeria = country(json!({ "id": 1, "name": "Nigeria", "region": "Africa" }));
+
+        assert_eq!(evaluate("region = AFRICA", &nigeria, &[]), Some(true));
+        assert_eq!(evaluate("region in (asia, africa)", &nigeria, &[]), Some(true));
+        assert_eq!(evaluate("name != nigeria", &nigeria, &[]), Some(false));
+    }
+
+    #[test]
+    fn currency_code_matches_any_currency_the_country_uses() {
+        let zimbabwe = country(json!({ "id": 1, "name": "Zimbabwe", "currency_code": "ZWL" }));
+        let codes = ["ZWL", "USD"];
+
+        assert_eq!(evaluate("currency_code = usd", &zimbabwe, &codes), Some(true));
+        assert_eq!(evaluate("currency_code != USD", &zimbabwe, &codes), Some(false));
+        assert_eq!(evaluate("currency_code != EUR", &zimbabwe, &codes), Some(true));
+        assert_eq!(evaluate("currency_code in (EUR, USD)", &zimbabwe, &codes), Some(true));
+        assert_eq!(evaluate("currency_code not in (EUR, GBP)", &zimbabwe, &codes), Some(true));
+        assert_eq!(evaluate("currency_code is null", &zimbabwe, &codes), Some(false));
+    }
+
+    #[test]
+    fn currency_code_is_unknown_without_a_currency() {
+        let antarctica = country(json!({ "id": 1, "name": "Antarctica" }));
+
+        assert_eq!(evaluate("currency_code = USD", &antarctica, &[]), None);
+        assert_eq!(evaluate("currency_code != USD", &antarctica, &[]), None);
+        assert_eq!(evaluate("not currency_code = USD", &antarctica, &[]), None);
+        assert_eq!(evaluate("currency_code is null", &antarctica, &[]), Some(true));
+    }
+}
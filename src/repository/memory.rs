@@ -0,0 +1,501 @@
+use std::cmp::Ordering;
+use std::sync::Mutex;
+use crate::config::db::DbResult;
+use crate::models::countries_models::Country;
+use crate::models::country_names::name_key;
+use crate::models::models::{CacheMetadata, Country as CountryModel, CountryCurrency, Currency as CurrencyModel, ExchangeRate, RefreshRun, RefreshRunRecord};
+use crate::repository::{currencies_to_upsert, rate_history, currency_links, CountryPage, CountryQuery, CountryRepository, Keyset, RefreshLock, SortField, SortKey};
+
+#[derive(Debug, Default)]
+struct MemoryStore {
+    countries: Vec<CountryModel>,
+    next_country_id: i32,
+    // (country id, alias key) pairs, like the `country_aliases` table.
+    aliases: Vec<(i32, String)>,
+    currencies: Vec<CurrencyModel>,
+    country_currencies: Vec<CountryCurrency>,
+    exchange_rates: Vec<ExchangeRate>,
+    cache_metadata: Vec<CacheMetadata>,
+    refresh_runs: Vec<RefreshRunRecord>,
+}
+
+/// Keeps everything in process memory, for demos and local development with no database.
+/// Data is lost on restart unless re-seeded from a snapshot.
+#[derive(Debug, Default)]
+pub struct MemoryRepository {
+    store: Mutex<MemoryStore>,
+}
+
+// Names are compared like MySQL's `utf8mb4_unicode_ci` collation would, ignoring case.
+fn same_name(stored: &Option<String>, wanted: &str) -> bool {
+    stored.as_ref().is_some_and(|n| n.to_lowercase() == wanted.to_lowercase())
+}
+
+impl MemoryStore {
+    fn uses_currency(&self, country_id: i32, code: &str) -> bool {
+        self.country_currencies.iter().any(|link| link.country_id == country_id && link.currency_code == code)
+    }
+
+    fn currency_codes(&self, country_id: i32) -> Vec<String> {
+        self.country_currencies.iter()
+            .filter(|link| link.country_id == country_id)
+            .map(|link| link.currency_code.clone())
+            .collect()
+    }
+
+    fn upsert_countries(&mut self, refreshed: &[Country]) {
+        for currency in currencies_to_upsert(refreshed) {
+            // Like the SQL backends, only the name and symbol of a known currency come from countries;
+            // its rate belongs to the rate table.
+            match self.currencies.iter_mut().find(|c| c.code == currency.code) {
+                Some(existing) => {
+                    existing.name = currency.name;
+                    existing.symbol = currency.symbol;
+                },
+                None => self.currencies.push(currency),
+            }
+        }
+
+        for country in refreshed.iter() {
+            let row = country.struct_to_upsert_country();
+            let existing = row.name.and_then(|n| self.countries.iter().position(|c| same_name(&c.name, n)));
+
+            let id = match existing {
+                Some(index) => self.countries[index].id,
+                None => {
+                    self.next_country_id += 1;
+                    self.next_country_id
+                }
+            };
+            let model = CountryModel {
+                id,
+                name: row.name.map(|s| s.to_string()),
+                capital: row.capital.map(|s| s.to_string()),
+                region: row.region.map(|s| s.to_string()),
+                population: row.population,
+                currency_code: row.currency_code.map(|s| s.to_string()),
+                exchange_rate: row.exchange_rate,
+                estimated_gdp: row.estimated_gdp,
+                flag_url: row.flag_url.map(|s| s.to_string()),
+                last_refreshed_at: row.last_refreshed_at,
+                alpha2_code: row.alpha2_code.map(|s| s.to_string()),
+                alpha3_code: row.alpha3_code.map(|s| s.to_string()),
+                numeric_code: row.numeric_code.map(|s| s.to_string()),
+                name_key: row.name_key,
+            };
+
+            self.aliases.retain(|(country_id, _)| *country_id != id);
+            self.aliases.extend(country.alias_keys().into_iter().map(|key| (id, key)));
+            self.country_currencies.retain(|link| link.country_id != id);
+            self.country_currencies.extend(currency_links(id, country));
+
+            match existing {
+                Some(index) => self.countries[index] = model,
+                None => self.countries.push(model),
+            }
+        }
+    }
+
+    fn upsert_rates(&mut self, rates: &[CurrencyModel]) {
+        for rate in rates {
+            match self.currencies.iter_mut().find(|c| c.code == rate.code) {
+                Some(currency) => {
+                    currency.exchange_rate = rate.exchange_rate;
+                    currency.rate_updated_at = rate.rate_updated_at;
+                    currency.rate_base = rate.rate_base.clone();
+                },
+                None => self.currencies.push(rate.clone()),
+            }
+        }
+        for row in rate_history(rates) {
+            let id = self.exchange_rates.len() as i32 + 1;
+            self.exchange_rates.push(ExchangeRate {
+                id,
+                currency_code: row.currency_code,
+                rate: row.rate,
+                rate_base: row.rate_base,
+                fetched_at: row.fetched_at,
+            });
+        }
+    }
+
+    // Same resolution order as the SQL backends: the folded name first, then alternate spellings.
+    fn find_by_name_key(&self, key: &str) -> Option<&CountryModel> {
+        self.countries.iter()
+            .find(|c| c.name.as_deref().map(name_key).as_deref() == Some(key))
+            .or_else(|| {
+                let (country_id, _) = self.aliases.iter().find(|(_, alias)| alias == key)?;
+                self.countries.iter().find(|c| c.id == *country_id)
+            })
+    }
+}
+
+// Orders like the SQL backends: NULLs last in both directions, text ignoring case,
+// and `f64::total_cmp` for doubles so NaN has a fixed place (above every number, as in PostgreSQL).
+fn compare_by(a: &CountryModel, b: &CountryModel, key: SortKey) -> Ordering {
+    fn nulls_last<T>(a: &Option<T>, b: &Option<T>, descending: bool, cmp: impl Fn(&T, &T) -> Ordering) -> Ordering {
+        match (a, b) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) if descending => cmp(b, a),
+            (Some(a), Some(b)) => cmp(a, b),
+        }
+    }
+    let text = |a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase());
+
+    match key.field {
+        SortField::Name => nulls_last(&a.name, &b.name, key.descending, text),
+        SortField::Capital => nulls_last(&a.capital, &b.capital, key.descending, text),
+        SortField::Region => nulls_last(&a.region, &b.region, key.descending, text),
+        SortField::Population => nulls_last(&a.population, &b.population, key.descending, |a, b| a.cmp(b)),
+        SortField::Gdp => nulls_last(&a.estimated_gdp, &b.estimated_gdp, key.descending, |a, b| a.total_cmp(b)),
+        SortField::ExchangeRate => nulls_last(&a.exchange_rate, &b.exchange_rate, key.descending, |a, b| a.total_cmp(b)),
+    }
+}
+
+// A row holding just the sort values of `keyset`, to compare stored rows against.
+fn keyset_row(keyset: &Keyset) -> CountryModel {
+    CountryModel {
+        id: keyset.id,
+        name: keyset.name.clone(),
+        capital: keyset.capital.clone(),
+        region: keyset.region.clone(),
+        population: keyset.population,
+        currency_code: None,
+        exchange_rate: keyset.exchange_rate,
+        estimated_gdp: keyset.estimated_gdp,
+        flag_url: None,
+        last_refreshed_at: None,
+        alpha2_code: None,
+        alpha3_code: None,
+        numeric_code: None,
+        name_key: None,
+    }
+}
+
+impl MemoryRepository {
+    /// Accepts `memory://` for an empty store or `memory://path/to/snapshot.json` to seed it
+    /// from a JSON array of country rows, in the shape the `countries` table stores them.
+    pub fn new(database_url: &str) -> Self {
+        let repo = MemoryRepository::default();
+
+        let snapshot_path = database_url
+            .strip_prefix("memory://")
+            .or_else(|| database_url.strip_prefix("memory:"))
+            .unwrap_or_default();
+
+        if !snapshot_path.is_empty() {
+            let snapshot = std::fs::read_to_string(snapshot_path)
+                .unwrap_or_else(|e| panic!("Error reading snapshot file {}: {e}", snapshot_path));
+            let seed: Vec<CountryModel> = serde_json::from_str(&snapshot)
+                .unwrap_or_else(|e| panic!("Error parsing snapshot file {}: {e}", snapshot_path));
+            println!("Seeded in-memory store with {} countries from {}", seed.len(), snapshot_path);
+            repo.seed(seed);
+        }
+
+        repo
+    }
+
+    fn seed(&self, seed: Vec<CountryModel>) {
+        let mut store = self.store.lock().unwrap();
+        store.next_country_id = seed.iter().map(|c| c.id).max().unwrap_or(0);
+        // Snapshots only carry each country's primary currency, as the migration backfill does.
+        for country in seed.iter() {
+            let Some(code) = &country.currency_code else { continue };
+            if !store.currencies.iter().any(|c| &c.code == code) {
+                store.currencies.push(CurrencyModel {
+                    code: code.clone(),
+                    name: None,
+                    symbol: None,
+                    exchange_rate: country.exchange_rate,
+                    rate_updated_at: country.last_refreshed_at,
+                    rate_base: None,
+                });
+            }
+            store.country_currencies.push(CountryCurrency { country_id: country.id, currency_code: code.clone(), position: 0 });
+        }
+        // History starts from the seeded rates, as it does from the stored ones after migrating.
+        store.exchange_rates = rate_history(&store.currencies)
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| ExchangeRate {
+                id: i as i32 + 1,
+                currency_code: row.currency_code,
+                rate: row.rate,
+                rate_base: row.rate_base,
+                fetched_at: row.fetched_at,
+            })
+            .collect();
+        store.countries = seed;
+    }
+}
+
+impl CountryRepository for MemoryRepository {
+    fn upsert_refresh(&self, refreshed: &[Country], rates: &[CurrencyModel]) -> DbResult<usize> {
+        // Both writes happen under one lock, so readers never see countries without their rates.
+        let mut store = self.store.lock().unwrap();
+        store.upsert_countries(refreshed);
+        store.upsert_rates(rates);
+        Ok(refreshed.len())
+    }
+
+    fn search_countries(&self, query: &CountryQuery) -> DbResult<CountryPage> {
+        let store = self.store.lock().unwrap();
+
+        let mut matched: Vec<CountryModel> = store.countries.iter()
+            .filter(|c| query.region.as_ref().is_none_or(|r| same_name(&c.region, r)))
+            .filter(|c| query.currency_code.as_ref().is_none_or(|code| store.uses_currency(c.id, &code.to_uppercase())))
+            .filter(|c| query.population.contains(c.population))
+            .filter(|c| query.estimated_gdp.contains(c.estimated_gdp))
+            .filter(|c| query.exchange_rate.contains(c.exchange_rate))
+            .filter(|c| query.filter.as_ref().is_none_or(|f| f.matches(c, &store.currency_codes(c.id)) == Some(true)))
+            .cloned()
+            .collect();
+
+        let order = |a: &CountryModel, b: &CountryModel| {
+            query.sort.iter()
+                .map(|key| compare_by(a, b, *key))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.id.cmp(&b.id))
+        };
+        matched.sort_by(order);
+
+        let total = matched.len() as i64;
+        let backwards = query.keyset.as_ref().is_some_and(|k| k.backwards);
+        if let Some(keyset) = &query.keyset {
+            let boundary = keyset_row(keyset);
+            matched.retain(|c| if backwards { order(c, &boundary).is_lt() } else { order(c, &boundary).is_gt() });
+        }
+        if backwards {
+            matched.reverse();
+        }
+        let mut countries: Vec<CountryModel> = matched.into_iter()
+            .skip(query.offset.max(0) as usize)
+            .take(query.limit.max(0) as usize)
+            .collect();
+        if backwards {
+            countries.reverse();
+        }
+
+        Ok(CountryPage { countries, total })
+    }
+
+    fn get_country_by_name(&self, country_name: &str) -> DbResult<Option<CountryModel>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.find_by_name_key(&name_key(country_name)).cloned())
+    }
+
+    fn get_country_by_id(&self, country_id: i32) -> DbResult<Option<CountryModel>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.countries.iter().find(|c| c.id == country_id).cloned())
+    }
+
+    fn get_country_by_code(&self, code: &str) -> DbResult<Option<CountryModel>> {
+        let store = self.store.lock().unwrap();
+        let code = Some(code);
+        Ok(store.countries.iter()
+            .find(|c| c.alpha2_code.as_deref() == code || c.alpha3_code.as_deref() == code || c.numeric_code.as_deref() == code)
+            .cloned())
+    }
+
+    fn get_country_names(&self) -> DbResult<Vec<String>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.countries.iter().filter_map(|c| c.name.clone()).collect())
+    }
+
+    fn get_exchange_rate_history(&self, code: &str, from: Option<chrono::NaiveDateTime>, to: Option<chrono::NaiveDateTime>) -> DbResult<Vec<ExchangeRate>> {
+        let store = self.store.lock().unwrap();
+        let mut history: Vec<ExchangeRate> = store.exchange_rates.iter()
+            .filter(|r| r.currency_code == code)
+            .filter(|r| from.is_none_or(|from| r.fetched_at >= from) && to.is_none_or(|to| r.fetched_at <= to))
+            .cloned()
+            .collect();
+        history.sort_by_key(|r| (r.fetched_at, r.id));
+        Ok(history)
+    }
+
+    fn get_currencies(&self) -> DbResult<Vec<CurrencyModel>> {
+        let store = self.store.lock().unwrap();
+        let mut currencies = store.currencies.clone();
+        currencies.sort_by(|a, b| a.code.cmp(&b.code));
+        Ok(currencies)
+    }
+
+    fn get_currency(&self, code: &str) -> DbResult<Option<CurrencyModel>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.currencies.iter().find(|c| c.code == code).cloned())
+    }
+
+    fn get_currency_countries(&self, codes: &[String]) -> DbResult<Vec<(String, CountryModel)>> {
+        let store = self.store.lock().unwrap();
+        let mut pairs: Vec<(String, CountryModel)> = store.country_currencies.iter()
+            .filter(|link| codes.contains(&link.currency_code))
+            .filter_map(|link| {
+                let country = store.countries.iter().find(|c| c.id == link.country_id)?;
+                Some((link.currency_code.clone(), country.clone()))
+            })
+            .collect();
+        pairs.sort_by(|(a_code, a), (b_code, b)| a_code.cmp(b_code).then_with(|| a.name.cmp(&b.name)));
+        Ok(pairs)
+    }
+
+    fn get_country_currencies(&self, country_ids: &[i32]) -> DbResult<Vec<(i32, CurrencyModel)>> {
+        let store = self.store.lock().unwrap();
+        let mut links: Vec<&CountryCurrency> = store.country_currencies.iter()
+            .filter(|link| country_ids.contains(&link.country_id))
+            .collect();
+        links.sort_by_key(|link| (link.country_id, link.position));
+        Ok(links.into_iter()
+            .filter_map(|link| {
+                let currency = store.currencies.iter().find(|c| c.code == link.currency_code)?;
+                Some((link.country_id, currency.clone()))
+            })
+            .collect())
+    }
+
+    fn delete_country_by_name(&self, country_name: &str) -> DbResult<usize> {
+        let mut store = self.store.lock().unwrap();
+        let Some(country_id) = store.find_by_name_key(&name_key(country_name)).map(|c| c.id) else {
+            return Ok(0);
+        };
+        store.countries.retain(|c| c.id != country_id);
+        store.aliases.retain(|(id, _)| *id != country_id);
+        store.country_currencies.retain(|link| link.country_id != country_id);
+        Ok(1)
+    }
+
+    fn save_summary_metadata(&self, metadata: CacheMetadata) -> DbResult<usize> {
+        self.store.lock().unwrap().cache_metadata.push(metadata);
+        Ok(1)
+    }
+
+    #[cfg(test)]
+    fn get_latest_summary_metadata(&self) -> DbResult<Option<CacheMetadata>> {
+        Ok(self.store.lock().unwrap().cache_metadata.last().cloned())
+    }
+
+    fn save_refresh_run(&self, run: RefreshRun) -> DbResult<usize> {
+        let mut store = self.store.lock().unwrap();
+        let id = store.refresh_runs.len() as i32 + 1;
+        store.refresh_runs.push(RefreshRunRecord {
+            id,
+            job_id: run.job_id,
+            triggered_by: run.triggered_by,
+            status: run.status,
+            countries_processed: run.countries_processed,
+            error: run.error,
+            started_at: run.started_at,
+            finished_at: run.finished_at,
+        });
+        Ok(1)
+    }
+
+    fn finish_refresh_run(&self, run: RefreshRun) -> DbResult<usize> {
+        let mut store = self.store.lock().unwrap();
+        let mut updated = 0;
+        for record in store.refresh_runs.iter_mut().filter(|r| r.job_id == run.job_id) {
+            record.status = run.status.clone();
+            record.countries_processed = run.countries_processed;
+            record.error = run.error.clone();
+            record.finished_at = run.finished_at;
+            updated += 1;
+        }
+        Ok(updated)
+    }
+
+    fn get_refresh_run_by_job_id(&self, refresh_job_id: &str) -> DbResult<Option<RefreshRunRecord>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.refresh_runs.iter().find(|r| r.job_id == refresh_job_id).cloned())
+    }
+
+    fn get_running_refresh_run(&self) -> DbResult<Option<RefreshRunRecord>> {
+        let store = self.store.lock().unwrap();
+        Ok(store.refresh_runs.iter()
+            .filter(|r| r.status == "running")
+            .max_by_key(|r| r.started_at)
+            .cloned())
+    }
+
+    fn fail_stale_refresh_runs(&self, error: &str) -> DbResult<usize> {
+        let mut store = self.store.lock().unwrap();
+        let now = chrono::Utc::now().naive_utc();
+        let mut updated = 0;
+        for record in store.refresh_runs.iter_mut().filter(|r| r.status == "running") {
+            record.status = "failed".to_string();
+            record.error = Some(error.to_string());
+            record.finished_at = Some(now);
+            updated += 1;
+        }
+        Ok(updated)
+    }
+
+    /// Only this process can see the store, so the job registry already serialises refreshes.
+    fn try_acquire_refresh_lock(&self) -> DbResult<Option<RefreshLock>> {
+        Ok(Some(RefreshLock::new(())))
+    }
+
+    /// There is no schema to migrate in memory.
+    fn run_pending_migrations(&self) -> DbResult<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn check_schema_version(&self) -> DbResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::test_support::{country, nigeria, rate};
+
+    #[test]
+    fn upserting_countries_keeps_the_stored_rate_of_a_known_currency() {
+        let repo = MemoryRepository::new("memory://");
+        repo.upsert_refresh(&[nigeria()], &[rate("NGN", 1460.5)]).unwrap();
+        repo.upsert_refresh(&[nigeria()], &[]).unwrap();
+
+        let naira = repo.get_currency("NGN").unwrap().unwrap();
+        assert_eq!(naira.name.as_deref(), Some("Nigerian naira"));
+        assert_eq!(naira.exchange_rate, Some(1460.5));
+        assert_eq!(naira.rate_base.as_deref(), Some("USD"));
+    }
+
+    #[test]
+    fn latest_summary_metadata_is_the_last_one_saved() {
+        let repo = MemoryRepository::new("memory://");
+        assert!(repo.get_latest_summary_metadata().unwrap().is_none());
+
+        let at = chrono::NaiveDate::from_ymd_opt(2025, 11, 5).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        repo.save_summary_metadata(CacheMetadata::new(250, &[], at)).unwrap();
+        repo.save_summary_metadata(CacheMetadata::new(249, &[nigeria().struct_to_upsert_country()], at)).unwrap();
+
+        let latest = repo.get_latest_summary_metadata().unwrap().unwrap();
+        assert_eq!(latest.total_countries, 249);
+        assert!(latest.top_countries_json.contains("Nigeria"));
+    }
+
+    #[test]
+    fn sorting_places_nan_above_numbers_and_nulls_last() {
+        let repo = MemoryRepository::new("memory://");
+        let countries: Vec<Country> = [("Chad", Some(5.0)), ("Mali", Some(f64::NAN)), ("Niger", None), ("Togo", Some(9.0))]
+            .into_iter()
+            .map(|(name, gdp)| {
+                let mut row = country(serde_json::json!({ "name": name }));
+                if let Some(gdp) = gdp {
+                    row.set_estimated_gdp(gdp);
+                }
+                row
+            })
+            .collect();
+        repo.upsert_refresh(&countries, &[]).unwrap();
+
+        let sorted = |descending: bool| {
+            let query = CountryQuery { sort: vec![SortKey { field: SortField::Gdp, descending }], limit: 10, ..CountryQuery::default() };
+            repo.search_countries(&query).unwrap().countries.into_iter().filter_map(|c| c.name).collect::<Vec<_>>()
+        };
+        assert_eq!(sorted(false), ["Chad", "Togo", "Mali", "Niger"]);
+        assert_eq!(sorted(true), ["Mali", "Togo", "Chad", "Niger"]);
+    }
+}
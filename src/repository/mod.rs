@@ -0,0 +1,503 @@
+use std::sync::Arc;
+use chrono::NaiveDateTime;
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+use diesel::backend::Backend;
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+use diesel::migration::MigrationSource;
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+use crate::config::db::DbError;
+use crate::config::db::DbResult;
+use crate::models::countries_models::Country;
+use crate::repository::filter::FilterExpr;
+use crate::models::models::{CacheMetadata, Country as CountryModel, CountryCurrency, Currency as CurrencyModel, ExchangeRate, NewExchangeRate, RefreshRun, RefreshRunRecord};
+
+pub mod filter;
+pub mod memory;
+#[cfg(feature = "mysql")]
+pub mod mysql;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+mod sql;
+
+// Name of the advisory lock guarding the refresh pipeline across every instance sharing the database.
+#[cfg(any(feature = "mysql", feature = "postgres"))]
+pub const REFRESH_LOCK_NAME: &str = "countries_api_refresh";
+
+// Rows per multi-row INSERT inside the refresh transaction.
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub const UPSERT_BATCH_SIZE: usize = 100;
+
+// MySQL compares case-insensitively through its collation; the other SQL backends compare `lower()` of both sides.
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+diesel::define_sql_function! {
+    fn lower(x: diesel::sql_types::Nullable<diesel::sql_types::Text>) -> diesel::sql_types::Nullable<diesel::sql_types::Text>;
+}
+
+/// Columns `/countries` can be sorted on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    Name,
+    Population,
+    Gdp,
+    ExchangeRate,
+    Capital,
+    Region,
+}
+
+/// One key of a multi-key sort. NULLs sort last in both directions, and
+/// rows that tie on every key fall back to `id` so consecutive pages never overlap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl SortField {
+    /// The response field holding this sort column.
+    pub fn field(&self) -> CountryField {
+        match self {
+            SortField::Name => CountryField::Name,
+            SortField::Population => CountryField::Population,
+            SortField::Gdp => CountryField::EstimatedGdp,
+            SortField::ExchangeRate => CountryField::ExchangeRate,
+            SortField::Capital => CountryField::Capital,
+            SortField::Region => CountryField::Region,
+        }
+    }
+}
+
+/// A position in the sort order of a [`CountryQuery`]: the sort values and `id` of one row.
+/// Only the columns named by the sort keys are read; a `None` among them is a NULL value.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Keyset {
+    pub id: i32,
+    pub name: Option<String>,
+    pub capital: Option<String>,
+    pub region: Option<String>,
+    pub population: Option<i64>,
+    pub estimated_gdp: Option<f64>,
+    pub exchange_rate: Option<f64>,
+    /// Page towards the start: rows before this position instead of after it.
+    pub backwards: bool,
+}
+
+impl Keyset {
+    /// The position of `row` under `sort`, keeping only the sorted columns.
+    pub fn at(row: &CountryModel, sort: &[SortKey], backwards: bool) -> Self {
+        let sorted = |field: SortField| sort.iter().any(|key| key.field == field);
+        Keyset {
+            id: row.id,
+            name: row.name.clone().filter(|_| sorted(SortField::Name)),
+            capital: row.capital.clone().filter(|_| sorted(SortField::Capital)),
+            region: row.region.clone().filter(|_| sorted(SortField::Region)),
+            population: row.population.filter(|_| sorted(SortField::Population)),
+            estimated_gdp: row.estimated_gdp.filter(|_| sorted(SortField::Gdp)),
+            exchange_rate: row.exchange_rate.filter(|_| sorted(SortField::ExchangeRate)),
+            backwards,
+        }
+    }
+}
+
+/// Columns of a country that a response can be projected to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CountryField {
+    Id,
+    Name,
+    Capital,
+    Region,
+    Population,
+    CurrencyCode,
+    /// Every currency the country uses, read from `country_currencies` rather than a column.
+    Currencies,
+    ExchangeRate,
+    EstimatedGdp,
+    FlagUrl,
+    LastRefreshedAt,
+    Alpha2Code,
+    Alpha3Code,
+    NumericCode,
+}
+
+/// Inclusive lower and upper bound on a numeric column; rows where the column is NULL never match a bound.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bounds<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T: PartialOrd + Copy> Bounds<T> {
+    pub fn contains(&self, value: Option<T>) -> bool {
+        match value {
+            Some(v) => self.min.is_none_or(|min| v >= min) && self.max.is_none_or(|max| v <= max),
+            None => self.min.is_none() && self.max.is_none(),
+        }
+    }
+}
+
+/// Filters, order and window for [`CountryRepository::search_countries`].
+#[derive(Debug, Clone, Default)]
+pub struct CountryQuery {
+    /// Matched case-insensitively.
+    pub region: Option<String>,
+    /// Matches a country using this currency in any position, not only its primary one.
+    pub currency_code: Option<String>,
+    pub population: Bounds<i64>,
+    pub estimated_gdp: Bounds<f64>,
+    pub exchange_rate: Bounds<f64>,
+    /// A parsed `filter=` expression, combined with the other filters by AND.
+    pub filter: Option<FilterExpr>,
+    /// Applied in order; empty means `id` order.
+    pub sort: Vec<SortKey>,
+    /// Columns the caller needs; `None` means all of them. Backends may leave the others unset.
+    pub fields: Option<Vec<CountryField>>,
+    /// Only rows after this position in the sort order, or before it when paging backwards.
+    /// Backwards pages hold the `limit` rows closest to the position, still returned in sort order.
+    pub keyset: Option<Keyset>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CountryPage {
+    pub countries: Vec<CountryModel>,
+    /// Rows matching the filters, ignoring `limit` and `offset`.
+    pub total: i64,
+}
+
+/// Adds the numeric range filters of a [`CountryQuery`] to a boxed `countries` query.
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+macro_rules! filter_ranges {
+    ($query:expr, $search:expr) => {{
+        use crate::schema::countries;
+
+        macro_rules! bounded {
+            ($q:expr, $column:expr, $bounds:expr) => {{
+                let mut q = $q;
+                if let Some(min) = $bounds.min {
+                    q = q.filter($column.ge(min));
+                }
+                if let Some(max) = $bounds.max {
+                    q = q.filter($column.le(max));
+                }
+                q
+            }};
+        }
+
+        let query = bounded!($query, countries::population, $search.population);
+        let query = bounded!(query, countries::estimated_gdp, $search.estimated_gdp);
+        bounded!(query, countries::exchange_rate, $search.exchange_rate)
+    }};
+}
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub(crate) use filter_ranges;
+
+/// Selects only the requested [`CountryField`]s from a boxed `countries` query, reading NULL in place of
+/// the rest so rows still load as a full `Country`. `id` is always selected.
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+macro_rules! select_fields {
+    ($query:expr, $backend:ty, $fields:expr) => {{
+        use crate::repository::CountryField;
+        use crate::schema::countries;
+        use diesel::sql_types::{BigInt, Double, Nullable, Timestamp, Varchar};
+
+        let fields: &Option<Vec<CountryField>> = $fields;
+        macro_rules! column {
+            ($field:expr, $column:expr, $sql_type:ty) => {
+                if fields.as_ref().map_or(true, |f| f.contains(&$field)) {
+                    Box::new($column) as Box<dyn BoxableExpression<countries::table, $backend, SqlType = Nullable<$sql_type>>>
+                } else {
+                    Box::new(diesel::dsl::sql::<Nullable<$sql_type>>("NULL"))
+                }
+            };
+        }
+
+        $query.select((
+            countries::id,
+            column!(CountryField::Name, countries::name, Varchar),
+            column!(CountryField::Capital, countries::capital, Varchar),
+            column!(CountryField::Region, countries::region, Varchar),
+            column!(CountryField::Population, countries::population, BigInt),
+            column!(CountryField::CurrencyCode, countries::currency_code, Varchar),
+            column!(CountryField::ExchangeRate, countries::exchange_rate, Double),
+            column!(CountryField::EstimatedGdp, countries::estimated_gdp, Double),
+            column!(CountryField::FlagUrl, countries::flag_url, Varchar),
+            column!(CountryField::LastRefreshedAt, countries::last_refreshed_at, Timestamp),
+            column!(CountryField::Alpha2Code, countries::alpha2_code, Varchar),
+            column!(CountryField::Alpha3Code, countries::alpha3_code, Varchar),
+            column!(CountryField::NumericCode, countries::numeric_code, Varchar),
+            countries::name_key,
+        ))
+    }};
+}
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub(crate) use select_fields;
+
+/// Appends `sort` and the `id` tie-break to a boxed `countries` query, or the exact reverse of that
+/// order when `$backwards` is true. Sorting on `col IS NULL` first keeps NULLs last on every backend,
+/// since MySQL has no `NULLS LAST`. A macro rather than a function because diesel does not let boxed
+/// queries be ordered generically over the backend.
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+macro_rules! order_countries {
+    ($query:expr, $sort:expr, $backwards:expr) => {{
+        use crate::repository::SortField;
+        use crate::schema::countries;
+
+        let backwards: bool = $backwards;
+        macro_rules! by {
+            ($q:expr, $column:expr, $descending:expr) => {{
+                let q = if backwards { $q.then_order_by($column.is_null().desc()) } else { $q.then_order_by($column.is_null().asc()) };
+                if $descending != backwards { q.then_order_by($column.desc()) } else { q.then_order_by($column.asc()) }
+            }};
+        }
+
+        let mut query = $query;
+        for key in $sort.iter() {
+            query = match key.field {
+                SortField::Name => by!(query, countries::name, key.descending),
+                SortField::Population => by!(query, countries::population, key.descending),
+                SortField::Gdp => by!(query, countries::estimated_gdp, key.descending),
+                SortField::ExchangeRate => by!(query, countries::exchange_rate, key.descending),
+                SortField::Capital => by!(query, countries::capital, key.descending),
+                SortField::Region => by!(query, countries::region, key.descending),
+            };
+        }
+        if backwards { query.then_order_by(countries::id.desc()) } else { query.then_order_by(countries::id.asc()) }
+    }};
+}
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub(crate) use order_countries;
+
+/// Rewrites the `country_aliases` and `country_currencies` rows of every country in `$refreshed`.
+/// Runs inside the refresh transaction, after the countries and their currencies have been upserted.
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+macro_rules! replace_country_links {
+    ($conn:expr, $refreshed:expr) => {{
+        use crate::models::models::{CountryAlias, CountryCurrency};
+        use crate::schema::{countries, country_aliases, country_currencies};
+
+        let refreshed: &[Country] = $refreshed;
+        let names: Vec<&str> = refreshed.iter().filter_map(|c| c.name.as_deref()).collect();
+        let stored: Vec<(i32, &Country)> = countries::table
+            .filter(countries::name.eq_any(&names))
+            .select((countries::id, countries::name))
+            .load::<(i32, Option<String>)>($conn)?
+            .into_iter()
+            .filter_map(|(id, name)| refreshed.iter().find(|c| c.name == name).map(|c| (id, c)))
+            .collect();
+
+        let ids: Vec<i32> = stored.iter().map(|(id, _)| *id).collect();
+        diesel::delete(country_aliases::table.filter(country_aliases::country_id.eq_any(&ids))).execute($conn)?;
+        diesel::delete(country_currencies::table.filter(country_currencies::country_id.eq_any(&ids))).execute($conn)?;
+
+        let aliases: Vec<CountryAlias> = stored
+            .iter()
+            .flat_map(|(country_id, c)| c.alias_keys().into_iter().map(|alias_key| CountryAlias { country_id: *country_id, alias_key }))
+            .collect();
+        for batch in aliases.chunks(crate::repository::UPSERT_BATCH_SIZE) {
+            diesel::insert_into(country_aliases::table).values(batch).execute($conn)?;
+        }
+
+        let links: Vec<CountryCurrency> = stored
+            .iter()
+            .flat_map(|(country_id, c)| crate::repository::currency_links(*country_id, c))
+            .collect();
+        for batch in links.chunks(crate::repository::UPSERT_BATCH_SIZE) {
+            diesel::insert_into(country_currencies::table).values(batch).execute($conn)?;
+        }
+    }};
+}
+
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub(crate) use replace_country_links;
+
+/// One `currencies` row per distinct code in `refreshed`, named after the first country that lists it.
+pub fn currencies_to_upsert(refreshed: &[Country]) -> Vec<CurrencyModel> {
+    let mut rows: Vec<CurrencyModel> = Vec::new();
+    for country in refreshed {
+        let refreshed_at = country.last_refreshed_at.as_ref().and_then(|ts| {
+            chrono::NaiveDateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S%.fZ").ok()
+        });
+        for currency in &country.currencies {
+            let Some(code) = &currency.code else { continue };
+            if rows.iter().any(|row| &row.code == code) {
+                continue;
+            }
+            rows.push(CurrencyModel {
+                code: code.clone(),
+                name: currency.name.clone(),
+                symbol: currency.symbol.clone(),
+                exchange_rate: currency.exchange_rate,
+                rate_updated_at: currency.exchange_rate.and(refreshed_at),
+                rate_base: None,
+            });
+        }
+    }
+    rows
+}
+
+/// The `exchange_rates` history rows for a rate table, skipping currencies without a rate or timestamp.
+pub fn rate_history(rates: &[CurrencyModel]) -> Vec<NewExchangeRate> {
+    rates.iter()
+        .filter_map(|currency| Some(NewExchangeRate {
+            currency_code: currency.code.clone(),
+            rate: currency.exchange_rate?,
+            rate_base: currency.rate_base.clone(),
+            fetched_at: currency.rate_updated_at?,
+        }))
+        .collect()
+}
+
+/// The `country_currencies` rows of one country, skipping currencies without a code and repeats.
+pub fn currency_links(country_id: i32, country: &Country) -> Vec<CountryCurrency> {
+    let mut links: Vec<CountryCurrency> = Vec::new();
+    for code in country.currencies.iter().filter_map(|c| c.code.clone()) {
+        if !links.iter().any(|link| link.currency_code == code) {
+            links.push(CountryCurrency { country_id, currency_code: code, position: links.len() as i32 });
+        }
+    }
+    links
+}
+
+/// Persistence operations used by the API, implemented once per storage backend.
+pub trait CountryRepository: Send + Sync + std::fmt::Debug {
+    /// Writes the whole refreshed dataset in one transaction, so a failed refresh leaves the previous
+    /// rows untouched: countries keyed on their unique name, then the latest rate of every currency in
+    /// `rates`, each also appended to the `exchange_rates` history. Countries only ever set a currency's
+    /// name and symbol; its rate comes from `rates`. Returns the number of countries written.
+    fn upsert_refresh(&self, refreshed: &[Country], rates: &[CurrencyModel]) -> DbResult<usize>;
+
+    /// Filters, orders and pages in the database, returning one window of rows plus the total match count.
+    fn search_countries(&self, query: &CountryQuery) -> DbResult<CountryPage>;
+
+    /// Resolves `country_name` ignoring case, accents and punctuation, first against country names
+    /// and then against their alternate spellings.
+    fn get_country_by_name(&self, country_name: &str) -> DbResult<Option<CountryModel>>;
+
+    fn get_country_by_id(&self, country_id: i32) -> DbResult<Option<CountryModel>>;
+
+    /// Finds the country whose ISO 3166 alpha-2, alpha-3 or numeric code equals `code`, which is stored upper-case.
+    fn get_country_by_code(&self, code: &str) -> DbResult<Option<CountryModel>>;
+
+    /// Every stored country name, for ranking "did you mean" suggestions.
+    fn get_country_names(&self) -> DbResult<Vec<String>>;
+
+    /// The stored rates of `code` fetched within `from..=to` (either end open), oldest first.
+    fn get_exchange_rate_history(&self, code: &str, from: Option<NaiveDateTime>, to: Option<NaiveDateTime>) -> DbResult<Vec<ExchangeRate>>;
+
+    /// Every stored currency, ordered by code.
+    fn get_currencies(&self) -> DbResult<Vec<CurrencyModel>>;
+
+    fn get_currency(&self, code: &str) -> DbResult<Option<CurrencyModel>>;
+
+    /// The countries using each of `codes`, as `(currency_code, country)` pairs ordered by code then country name.
+    fn get_currency_countries(&self, codes: &[String]) -> DbResult<Vec<(String, CountryModel)>>;
+
+    /// The currencies of each of `country_ids`, as `(country_id, currency)` pairs in each country's own order.
+    fn get_country_currencies(&self, country_ids: &[i32]) -> DbResult<Vec<(i32, CurrencyModel)>>;
+
+    fn delete_country_by_name(&self, country_name: &str) -> DbResult<usize>;
+
+    fn save_summary_metadata(&self, metadata: CacheMetadata) -> DbResult<usize>;
+
+    /// The metadata of the most recently saved summary, if any refresh has written one.
+    #[cfg(test)]
+    fn get_latest_summary_metadata(&self) -> DbResult<Option<CacheMetadata>>;
+
+    fn save_refresh_run(&self, run: RefreshRun) -> DbResult<usize>;
+
+    fn finish_refresh_run(&self, run: RefreshRun) -> DbResult<usize>;
+
+    fn get_refresh_run_by_job_id(&self, refresh_job_id: &str) -> DbResult<Option<RefreshRunRecord>>;
+
+    fn get_running_refresh_run(&self) -> DbResult<Option<RefreshRunRecord>>;
+
+    /// Marks every run still recorded as `running` as failed with `error`, returning how many were closed.
+    /// Only call this while holding the refresh lock, so a run in progress elsewhere is left alone.
+    fn fail_stale_refresh_runs(&self, error: &str) -> DbResult<usize>;
+
+    /// Tries to take the cross-instance refresh lock without waiting.
+    /// Returns `None` when another holder (possibly on another replica) already has it.
+    fn try_acquire_refresh_lock(&self) -> DbResult<Option<RefreshLock>>;
+
+    /// Applies the migrations embedded in the binary that the database has not run yet,
+    /// returning the versions applied.
+    fn run_pending_migrations(&self) -> DbResult<Vec<String>>;
+
+    /// Fails when the database has migrations applied that are newer than any this binary embeds.
+    fn check_schema_version(&self) -> DbResult<()>;
+}
+
+/// Keeps the refresh lock held until dropped. Each backend supplies its own guard,
+/// e.g. the dedicated connection that owns a MySQL `GET_LOCK`.
+pub struct RefreshLock {
+    _guard: Box<dyn Send>,
+}
+
+impl RefreshLock {
+    pub fn new(guard: impl Send + 'static) -> Self {
+        RefreshLock { _guard: Box::new(guard) }
+    }
+}
+
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub fn run_embedded_migrations<DB: Backend>(conn: &mut impl MigrationHarness<DB>, source: EmbeddedMigrations) -> DbResult<Vec<String>> {
+    let applied = conn
+        .run_pending_migrations(source)
+        .map_err(|e| DbError::Migration(e.to_string()))?;
+    Ok(applied.iter().map(|version| version.to_string()).collect())
+}
+
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+pub fn check_embedded_migrations<DB: Backend>(conn: &mut impl MigrationHarness<DB>, source: EmbeddedMigrations) -> DbResult<()> {
+    let known: Vec<String> = MigrationSource::<DB>::migrations(&source)
+        .map_err(|e| DbError::Migration(e.to_string()))?
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .collect();
+    let applied: Vec<String> = conn
+        .applied_migrations()
+        .map_err(|e| DbError::Migration(e.to_string()))?
+        .iter()
+        .map(|version| version.to_string())
+        .collect();
+
+    let latest_known = known.iter().max().cloned().unwrap_or_default();
+    let newer: Vec<&String> = applied.iter()
+        .filter(|version| !known.contains(version) && **version > latest_known)
+        .collect();
+
+    if newer.is_empty() {
+        Ok(())
+    } else {
+        Err(DbError::Migration(format!(
+            "database has migrations applied ({}) that are newer than this binary understands (latest known: {})",
+            newer.iter().map(|v| v.as_str()).collect::<Vec<_>>().join(", "),
+            latest_known
+        )))
+    }
+}
+
+/// Picks the backend from the `DATABASE_URL` scheme; the matching cargo feature must be enabled.
+/// `memory://` needs no feature and no database.
+pub fn connect(database_url: &str) -> Arc<dyn CountryRepository> {
+    let scheme = database_url.split(':').next().unwrap_or_default();
+
+    match scheme {
+        #[cfg(feature = "mysql")]
+        "mysql" => Arc::new(mysql::MysqlRepository::new(database_url)),
+        #[cfg(feature = "postgres")]
+        "postgres" | "postgresql" => Arc::new(postgres::PostgresRepository::new(database_url)),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Arc::new(sqlite::SqliteRepository::new(database_url)),
+        "memory" => Arc::new(memory::MemoryRepository::new(database_url)),
+        _ => panic!(
+            "DATABASE_URL scheme '{}' is not supported by this build (enable the matching `mysql`, `postgres` or `sqlite` feature, or use `memory://`)",
+            scheme
+        ),
+    }
+}
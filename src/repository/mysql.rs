@@ -0,0 +1,132 @@
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+use diesel::mysql::Mysql;
+use diesel_migrations::{EmbeddedMigrations, embed_migrations};
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use diesel::dsl::sql;
+use diesel::sql_types::{BigInt, Double, Nullable, Timestamp, Varchar};
+use crate::config::db::{DbResult, pool_builder};
+use crate::models::models::{Currency as CurrencyModel, UpsertCountry};
+use crate::repository::{sql::sql_repository, RefreshLock, REFRESH_LOCK_NAME, UPSERT_BATCH_SIZE};
+use crate::schema::{countries, currencies};
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+sql_repository!(MysqlRepository, Mysql, false);
+
+type PooledConn = PooledConnection<ConnectionManager<MysqlConnection>>;
+
+#[derive(Debug, Clone)]
+pub struct MysqlRepository {
+    pool: Pool<ConnectionManager<MysqlConnection>>,
+    database_url: String,
+}
+
+#[derive(QueryableByName)]
+struct LockResult {
+    #[diesel(sql_type = Nullable<diesel::sql_types::Integer>)]
+    acquired: Option<i32>,
+}
+
+/// The connection that owns the `GET_LOCK`. It is opened outside the pool and closed with the guard,
+/// so a failed `RELEASE_LOCK` cannot leave the lock held by a pooled connection.
+struct MysqlLockGuard {
+    conn: MysqlConnection,
+}
+
+impl Drop for MysqlLockGuard {
+    fn drop(&mut self) {
+        if let Err(e) = diesel::sql_query("SELECT RELEASE_LOCK(?)")
+            .bind::<diesel::sql_types::Text, _>(REFRESH_LOCK_NAME)
+            .execute(&mut self.conn)
+        {
+            println!("Error releasing refresh lock, closing its connection: {}", e);
+        }
+    }
+}
+
+impl MysqlRepository {
+    pub fn new(database_url: &str) -> Self {
+        let manager = ConnectionManager::<MysqlConnection>::new(database_url);
+        MysqlRepository { pool: pool_builder().build_unchecked(manager), database_url: database_url.to_string() }
+    }
+
+    fn conn(&self) -> DbResult<PooledConn> {
+        Ok(self.pool.get()?)
+    }
+
+    /// Upserts currency names and symbols with `INSERT ... ON DUPLICATE KEY UPDATE`,
+    /// leaving stored rates alone.
+    fn upsert_currency_names(conn: &mut PooledConn, rows: &[CurrencyModel]) -> QueryResult<()> {
+        for batch in rows.chunks(UPSERT_BATCH_SIZE) {
+            diesel::insert_into(currencies::table)
+                .values(batch)
+                .on_conflict(diesel::dsl::DuplicatedKeys)
+                .do_update()
+                .set((
+                    currencies::name.eq(sql::<Nullable<Varchar>>("VALUES(name)")),
+                    currencies::symbol.eq(sql::<Nullable<Varchar>>("VALUES(symbol)")),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Uses `INSERT ... ON DUPLICATE KEY UPDATE` against `uniq_name`.
+    fn upsert_country_rows(conn: &mut PooledConn, rows: &[UpsertCountry]) -> QueryResult<()> {
+        for batch in rows.chunks(UPSERT_BATCH_SIZE) {
+            println!("Upserting batch of {} countries", batch.len());
+            diesel::insert_into(countries::table)
+                .values(batch)
+                .on_conflict(diesel::dsl::DuplicatedKeys)
+                .do_update()
+                .set((
+                    countries::capital.eq(sql::<Nullable<Varchar>>("VALUES(capital)")),
+                    countries::region.eq(sql::<Nullable<Varchar>>("VALUES(region)")),
+                    countries::population.eq(sql::<Nullable<BigInt>>("VALUES(population)")),
+                    countries::currency_code.eq(sql::<Nullable<Varchar>>("VALUES(currency_code)")),
+                    countries::exchange_rate.eq(sql::<Nullable<Double>>("VALUES(exchange_rate)")),
+                    countries::estimated_gdp.eq(sql::<Nullable<Double>>("VALUES(estimated_gdp)")),
+                    countries::flag_url.eq(sql::<Nullable<Varchar>>("VALUES(flag_url)")),
+                    countries::last_refreshed_at.eq(sql::<Nullable<Timestamp>>("VALUES(last_refreshed_at)")),
+                    countries::alpha2_code.eq(sql::<Nullable<Varchar>>("VALUES(alpha2_code)")),
+                    countries::alpha3_code.eq(sql::<Nullable<Varchar>>("VALUES(alpha3_code)")),
+                    countries::numeric_code.eq(sql::<Nullable<Varchar>>("VALUES(numeric_code)")),
+                    countries::name_key.eq(sql::<Nullable<Varchar>>("VALUES(name_key)")),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Upserts the current rate of each currency with `INSERT ... ON DUPLICATE KEY UPDATE`.
+    fn upsert_rate_rows(conn: &mut PooledConn, rows: &[CurrencyModel]) -> QueryResult<()> {
+        for batch in rows.chunks(UPSERT_BATCH_SIZE) {
+            diesel::insert_into(currencies::table)
+                .values(batch)
+                .on_conflict(diesel::dsl::DuplicatedKeys)
+                .do_update()
+                .set((
+                    currencies::exchange_rate.eq(sql::<Nullable<Double>>("VALUES(exchange_rate)")),
+                    currencies::rate_updated_at.eq(sql::<Nullable<Timestamp>>("VALUES(rate_updated_at)")),
+                    currencies::rate_base.eq(sql::<Nullable<Varchar>>("VALUES(rate_base)")),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Uses a MySQL `GET_LOCK` advisory lock held on a dedicated connection outside the pool.
+    fn acquire_refresh_lock(&self) -> DbResult<Option<RefreshLock>> {
+        let mut conn = MysqlConnection::establish(&self.database_url)?;
+
+        let result = diesel::sql_query("SELECT GET_LOCK(?, 0) AS acquired")
+            .bind::<diesel::sql_types::Text, _>(REFRESH_LOCK_NAME)
+            .get_result::<LockResult>(&mut conn)?;
+
+        match result.acquired {
+            Some(1) => Ok(Some(RefreshLock::new(MysqlLockGuard { conn }))),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::pg::Pg;
+use diesel_migrations::{EmbeddedMigrations, embed_migrations};
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use diesel::upsert::excluded;
+use crate::config::db::{DbResult, pool_builder};
+use crate::models::models::{Currency as CurrencyModel, UpsertCountry};
+use crate::repository::{sql::sql_repository, RefreshLock, REFRESH_LOCK_NAME, UPSERT_BATCH_SIZE};
+use crate::schema::{countries, currencies};
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_postgres");
+
+sql_repository!(PostgresRepository, Pg, true);
+
+type PooledConn = PooledConnection<ConnectionManager<PgConnection>>;
+
+#[derive(Debug, Clone)]
+pub struct PostgresRepository {
+    pool: Pool<ConnectionManager<PgConnection>>,
+    database_url: String,
+}
+
+#[derive(QueryableByName)]
+struct LockResult {
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    acquired: bool,
+}
+
+/// The session that owns the advisory lock. It is opened outside the pool and closed with the guard,
+/// so a failed `pg_advisory_unlock` cannot leave the lock held by a pooled session.
+struct PostgresLockGuard {
+    conn: PgConnection,
+}
+
+impl Drop for PostgresLockGuard {
+    fn drop(&mut self) {
+        if let Err(e) = diesel::sql_query("SELECT pg_advisory_unlock(hashtext($1))")
+            .bind::<diesel::sql_types::Text, _>(REFRESH_LOCK_NAME)
+            .execute(&mut self.conn)
+        {
+            println!("Error releasing refresh lock, closing its connection: {}", e);
+        }
+    }
+}
+
+impl PostgresRepository {
+    pub fn new(database_url: &str) -> Self {
+        let manager = ConnectionManager::<PgConnection>::new(database_url);
+        PostgresRepository { pool: pool_builder().build_unchecked(manager), database_url: database_url.to_string() }
+    }
+
+    fn conn(&self) -> DbResult<PooledConn> {
+        Ok(self.pool.get()?)
+    }
+
+    /// Upserts currency names and symbols with `INSERT ... ON CONFLICT (code) DO UPDATE`,
+    /// leaving stored rates alone.
+    fn upsert_currency_names(conn: &mut PooledConn, rows: &[CurrencyModel]) -> QueryResult<()> {
+        for batch in rows.chunks(UPSERT_BATCH_SIZE) {
+            diesel::insert_into(currencies::table)
+                .values(batch)
+                .on_conflict(currencies::code)
+                .do_update()
+                .set((
+                    currencies::name.eq(excluded(currencies::name)),
+                    currencies::symbol.eq(excluded(currencies::symbol)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Uses `INSERT ... ON CONFLICT (name) DO UPDATE`.
+    fn upsert_country_rows(conn: &mut PooledConn, rows: &[UpsertCountry]) -> QueryResult<()> {
+        for batch in rows.chunks(UPSERT_BATCH_SIZE) {
+            println!("Upserting batch of {} countries", batch.len());
+            diesel::insert_into(countries::table)
+                .values(batch)
+                .on_conflict(countries::name)
+                .do_update()
+                .set((
+                    countries::capital.eq(excluded(countries::capital)),
+                    countries::region.eq(excluded(countries::region)),
+                    countries::population.eq(excluded(countries::population)),
+                    countries::currency_code.eq(excluded(countries::currency_code)),
+                    countries::exchange_rate.eq(excluded(countries::exchange_rate)),
+                    countries::estimated_gdp.eq(excluded(countries::estimated_gdp)),
+                    countries::flag_url.eq(excluded(countries::flag_url)),
+                    countries::last_refreshed_at.eq(excluded(countries::last_refreshed_at)),
+                    countries::alpha2_code.eq(excluded(countries::alpha2_code)),
+                    countries::alpha3_code.eq(excluded(countries::alpha3_code)),
+                    countries::numeric_code.eq(excluded(countries::numeric_code)),
+                    countries::name_key.eq(excluded(countries::name_key)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Upserts the current rate of each currency with `INSERT ... ON CONFLICT (code) DO UPDATE`.
+    fn upsert_rate_rows(conn: &mut PooledConn, rows: &[CurrencyModel]) -> QueryResult<()> {
+        for batch in rows.chunks(UPSERT_BATCH_SIZE) {
+            diesel::insert_into(currencies::table)
+                .values(batch)
+                .on_conflict(currencies::code)
+                .do_update()
+                .set((
+                    currencies::exchange_rate.eq(excluded(currencies::exchange_rate)),
+                    currencies::rate_updated_at.eq(excluded(currencies::rate_updated_at)),
+                    currencies::rate_base.eq(excluded(currencies::rate_base)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Uses a session-level `pg_try_advisory_lock` held on a dedicated connection outside the pool.
+    fn acquire_refresh_lock(&self) -> DbResult<Option<RefreshLock>> {
+        let mut conn = PgConnection::establish(&self.database_url)?;
+
+        let result = diesel::sql_query("SELECT pg_try_advisory_lock(hashtext($1)) AS acquired")
+            .bind::<diesel::sql_types::Text, _>(REFRESH_LOCK_NAME)
+            .get_result::<LockResult>(&mut conn)?;
+
+        if result.acquired {
+            Ok(Some(RefreshLock::new(PostgresLockGuard { conn })))
+        } else {
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,356 @@
+/// Implements [`CountryRepository`](crate::repository::CountryRepository) for a diesel backend.
+///
+/// Every query that reads the same on MySQL, PostgreSQL and SQLite lives here once. The invoking
+/// module supplies what differs between dialects, as inherent items of `$repo`:
+/// `conn()`, `upsert_currency_names`, `upsert_country_rows`, `upsert_rate_rows` and
+/// `acquire_refresh_lock`, plus a `MIGRATIONS` constant. `case_insensitive_text` is passed on to
+/// `filter_compiler!` and also decides whether `region=` compares through `lower()`.
+macro_rules! sql_repository {
+    ($repo:ty, $backend:ty, $case_insensitive_text:expr) => {
+        crate::repository::filter::filter_compiler!($backend, $case_insensitive_text);
+
+        mod queries {
+            // `MIGRATIONS`, `compile_filter`, `PooledConn` and the repository type come from the invoking module.
+            use super::*;
+            use chrono::NaiveDateTime;
+            use crate::config::db::{DbError, DbResult};
+            use crate::models::countries_models::Country;
+            use crate::models::country_names::name_key;
+            use crate::models::models::{CacheMetadata, Country as CountryModel, Currency as CurrencyModel, ExchangeRate, RefreshRun, RefreshRunRecord, UpsertCountry};
+            use crate::repository::{currencies_to_upsert, rate_history, CountryPage, CountryQuery, CountryRepository, filter_ranges, Keyset, lower, order_countries, replace_country_links, select_fields, SortField, SortKey, check_embedded_migrations, run_embedded_migrations, RefreshLock, UPSERT_BATCH_SIZE};
+            use crate::schema::{cache_metadata, countries, country_aliases, country_currencies, currencies, exchange_rates, refresh_runs};
+
+            // Rows strictly past `keyset` in the order `order_countries!` gives `sort`: compared key by key,
+            // with NULLs last (first when paging backwards) and `id` breaking ties.
+            fn keyset_filter(sort: &[SortKey], keyset: &Keyset) -> BoxedFilter {
+                use diesel::IntoSql;
+                use diesel::sql_types::Bool;
+
+                let backwards = keyset.backwards;
+                macro_rules! past {
+                    ($column:expr, $value:expr, $descending:expr) => {
+                        match $value.clone() {
+                            None if backwards => Box::new($column.is_not_null().nullable()) as BoxedFilter,
+                            None => Box::new(false.into_sql::<Bool>().nullable()),
+                            Some(v) => {
+                                let beyond: BoxedFilter = if $descending != backwards { Box::new($column.lt(v)) } else { Box::new($column.gt(v)) };
+                                if backwards { beyond } else { Box::new(beyond.or($column.is_null())) }
+                            },
+                        }
+                    };
+                }
+                macro_rules! level {
+                    ($column:expr, $value:expr) => {
+                        match $value.clone() {
+                            None => Box::new($column.is_null().nullable()) as BoxedFilter,
+                            Some(v) => Box::new($column.eq(v)),
+                        }
+                    };
+                }
+                macro_rules! key {
+                    ($column:expr, $value:expr, $descending:expr) => {
+                        (past!($column, $value, $descending), level!($column, $value))
+                    };
+                }
+
+                let mut filter: BoxedFilter = if backwards {
+                    Box::new(countries::id.lt(keyset.id).nullable())
+                } else {
+                    Box::new(countries::id.gt(keyset.id).nullable())
+                };
+                for sort_key in sort.iter().rev() {
+                    let (past, level) = match sort_key.field {
+                        SortField::Name => key!(countries::name, keyset.name, sort_key.descending),
+                        SortField::Capital => key!(countries::capital, keyset.capital, sort_key.descending),
+                        SortField::Region => key!(countries::region, keyset.region, sort_key.descending),
+                        SortField::Population => key!(countries::population, keyset.population, sort_key.descending),
+                        SortField::Gdp => key!(countries::estimated_gdp, keyset.estimated_gdp, sort_key.descending),
+                        SortField::ExchangeRate => key!(countries::exchange_rate, keyset.exchange_rate, sort_key.descending),
+                    };
+                    filter = Box::new(past.or(level.and(filter)));
+                }
+                filter
+            }
+
+            impl CountryRepository for $repo {
+                fn upsert_refresh(&self, refreshed: &[Country], rates: &[CurrencyModel]) -> DbResult<usize> {
+                    let mut conn = self.conn()?;
+
+                    let upsert_data: Vec<UpsertCountry> = refreshed.iter().map(|c| c.struct_to_upsert_country()).collect();
+                    let currency_data: Vec<CurrencyModel> = currencies_to_upsert(refreshed);
+
+                    conn.transaction(|conn| {
+                        <$repo>::upsert_currency_names(conn, &currency_data)?;
+                        <$repo>::upsert_country_rows(conn, &upsert_data)?;
+                        replace_country_links!(conn, refreshed);
+                        <$repo>::upsert_rate_rows(conn, rates)?;
+                        for batch in rate_history(rates).chunks(UPSERT_BATCH_SIZE) {
+                            diesel::insert_into(exchange_rates::table).values(batch).execute(conn)?;
+                        }
+                        Ok::<usize, diesel::result::Error>(upsert_data.len())
+                    })
+                    .map_err(DbError::from)
+                }
+
+                fn search_countries(&self, query: &CountryQuery) -> DbResult<CountryPage> {
+                    let mut conn = self.conn()?;
+
+                    let filtered = || {
+                        let mut q = countries::table.into_boxed::<$backend>();
+                        if let Some(region) = &query.region {
+                            q = if $case_insensitive_text {
+                                q.filter(lower(countries::region).eq(region.to_lowercase()))
+                            } else {
+                                q.filter(countries::region.eq(region))
+                            };
+                        }
+                        if let Some(code) = &query.currency_code {
+                            // Currency codes are stored upper-case, as in `compile_filter`.
+                            let using = country_currencies::table
+                                .filter(country_currencies::currency_code.eq(code.to_uppercase()))
+                                .select(country_currencies::country_id);
+                            q = q.filter(countries::id.eq_any(using));
+                        }
+                        if let Some(expr) = &query.filter {
+                            q = q.filter(compile_filter(expr));
+                        }
+                        filter_ranges!(q, query)
+                    };
+
+                    let total = filtered().count().get_result::<i64>(&mut conn)?;
+                    let mut windowed = filtered();
+                    if let Some(keyset) = &query.keyset {
+                        windowed = windowed.filter(keyset_filter(&query.sort, keyset));
+                    }
+                    let backwards = query.keyset.as_ref().is_some_and(|k| k.backwards);
+                    let ordered = order_countries!(windowed, query.sort, backwards);
+                    let mut countries = select_fields!(ordered, $backend, &query.fields)
+                        .limit(query.limit)
+                        .offset(query.offset)
+                        .load::<CountryModel>(&mut conn)?;
+                    if backwards {
+                        countries.reverse();
+                    }
+
+                    Ok(CountryPage { countries, total })
+                }
+
+                fn get_country_by_name(&self, country_name: &str) -> DbResult<Option<CountryModel>> {
+                    let mut conn = self.conn()?;
+                    let key = name_key(country_name);
+
+                    let by_name = countries::table
+                        .filter(countries::name_key.eq(&key))
+                        .order(countries::id)
+                        .first::<CountryModel>(&mut conn)
+                        .optional()?;
+                    if by_name.is_some() {
+                        return Ok(by_name);
+                    }
+
+                    let aliased = country_aliases::table
+                        .filter(country_aliases::alias_key.eq(&key))
+                        .select(country_aliases::country_id);
+                    countries::table
+                        .filter(countries::id.eq_any(aliased))
+                        .order(countries::id)
+                        .first::<CountryModel>(&mut conn)
+                        .optional()
+                        .map_err(DbError::from)
+                }
+
+                fn get_country_by_id(&self, country_id: i32) -> DbResult<Option<CountryModel>> {
+                    let mut conn = self.conn()?;
+                    countries::table
+                        .find(country_id)
+                        .first::<CountryModel>(&mut conn)
+                        .optional()
+                        .map_err(DbError::from)
+                }
+
+                fn get_country_by_code(&self, code: &str) -> DbResult<Option<CountryModel>> {
+                    let mut conn = self.conn()?;
+                    countries::table
+                        .filter(
+                            countries::alpha2_code.eq(code)
+                                .or(countries::alpha3_code.eq(code))
+                                .or(countries::numeric_code.eq(code)),
+                        )
+                        .first::<CountryModel>(&mut conn)
+                        .optional()
+                        .map_err(DbError::from)
+                }
+
+                fn get_country_names(&self) -> DbResult<Vec<String>> {
+                    let mut conn = self.conn()?;
+                    let names = countries::table
+                        .select(countries::name)
+                        .order(countries::id)
+                        .load::<Option<String>>(&mut conn)?;
+                    Ok(names.into_iter().flatten().collect())
+                }
+
+                fn get_exchange_rate_history(&self, code: &str, from: Option<NaiveDateTime>, to: Option<NaiveDateTime>) -> DbResult<Vec<ExchangeRate>> {
+                    let mut conn = self.conn()?;
+                    let mut query = exchange_rates::table
+                        .filter(exchange_rates::currency_code.eq(code))
+                        .into_boxed();
+                    if let Some(from) = from {
+                        query = query.filter(exchange_rates::fetched_at.ge(from));
+                    }
+                    if let Some(to) = to {
+                        query = query.filter(exchange_rates::fetched_at.le(to));
+                    }
+                    query
+                        .order((exchange_rates::fetched_at, exchange_rates::id))
+                        .select(ExchangeRate::as_select())
+                        .load::<ExchangeRate>(&mut conn)
+                        .map_err(DbError::from)
+                }
+
+                fn get_currencies(&self) -> DbResult<Vec<CurrencyModel>> {
+                    let mut conn = self.conn()?;
+                    currencies::table
+                        .order(currencies::code)
+                        .select(CurrencyModel::as_select())
+                        .load::<CurrencyModel>(&mut conn)
+                        .map_err(DbError::from)
+                }
+
+                fn get_currency(&self, code: &str) -> DbResult<Option<CurrencyModel>> {
+                    let mut conn = self.conn()?;
+                    currencies::table
+                        .find(code)
+                        .select(CurrencyModel::as_select())
+                        .first::<CurrencyModel>(&mut conn)
+                        .optional()
+                        .map_err(DbError::from)
+                }
+
+                fn get_currency_countries(&self, codes: &[String]) -> DbResult<Vec<(String, CountryModel)>> {
+                    let mut conn = self.conn()?;
+                    country_currencies::table
+                        .inner_join(countries::table.on(countries::id.eq(country_currencies::country_id)))
+                        .filter(country_currencies::currency_code.eq_any(codes))
+                        .order((country_currencies::currency_code, countries::name))
+                        .select((country_currencies::currency_code, CountryModel::as_select()))
+                        .load::<(String, CountryModel)>(&mut conn)
+                        .map_err(DbError::from)
+                }
+
+                fn get_country_currencies(&self, country_ids: &[i32]) -> DbResult<Vec<(i32, CurrencyModel)>> {
+                    let mut conn = self.conn()?;
+                    country_currencies::table
+                        .inner_join(currencies::table)
+                        .filter(country_currencies::country_id.eq_any(country_ids))
+                        .order((country_currencies::country_id, country_currencies::position))
+                        .select((country_currencies::country_id, CurrencyModel::as_select()))
+                        .load::<(i32, CurrencyModel)>(&mut conn)
+                        .map_err(DbError::from)
+                }
+
+                fn delete_country_by_name(&self, country_name: &str) -> DbResult<usize> {
+                    let Some(country) = self.get_country_by_name(country_name)? else {
+                        return Ok(0);
+                    };
+
+                    let mut conn = self.conn()?;
+                    conn.transaction(|conn| {
+                        diesel::delete(country_aliases::table.filter(country_aliases::country_id.eq(country.id))).execute(conn)?;
+                        diesel::delete(country_currencies::table.filter(country_currencies::country_id.eq(country.id))).execute(conn)?;
+                        diesel::delete(countries::table.find(country.id)).execute(conn)
+                    })
+                    .map_err(DbError::from)
+                }
+
+                fn save_summary_metadata(&self, metadata: CacheMetadata) -> DbResult<usize> {
+                    let mut conn = self.conn()?;
+                    diesel::insert_into(cache_metadata::table)
+                        .values(&metadata)
+                        .execute(&mut conn)
+                        .map_err(DbError::from)
+                }
+
+                #[cfg(test)]
+                fn get_latest_summary_metadata(&self) -> DbResult<Option<CacheMetadata>> {
+                    let mut conn = self.conn()?;
+                    cache_metadata::table
+                        .order(cache_metadata::id.desc())
+                        .select((cache_metadata::file_path, cache_metadata::total_countries, cache_metadata::top_countries_json, cache_metadata::last_refreshed_at))
+                        .first::<CacheMetadata>(&mut conn)
+                        .optional()
+                        .map_err(DbError::from)
+                }
+
+                fn save_refresh_run(&self, run: RefreshRun) -> DbResult<usize> {
+                    let mut conn = self.conn()?;
+                    diesel::insert_into(refresh_runs::table)
+                        .values(&run)
+                        .execute(&mut conn)
+                        .map_err(DbError::from)
+                }
+
+                fn finish_refresh_run(&self, run: RefreshRun) -> DbResult<usize> {
+                    let mut conn = self.conn()?;
+                    diesel::update(refresh_runs::table.filter(refresh_runs::job_id.eq(&run.job_id)))
+                        .set((
+                            refresh_runs::status.eq(&run.status),
+                            refresh_runs::countries_processed.eq(run.countries_processed),
+                            refresh_runs::error.eq(&run.error),
+                            refresh_runs::finished_at.eq(run.finished_at),
+                        ))
+                        .execute(&mut conn)
+                        .map_err(DbError::from)
+                }
+
+                fn get_refresh_run_by_job_id(&self, refresh_job_id: &str) -> DbResult<Option<RefreshRunRecord>> {
+                    let mut conn = self.conn()?;
+                    refresh_runs::table
+                        .filter(refresh_runs::job_id.eq(refresh_job_id))
+                        .select(RefreshRunRecord::as_select())
+                        .first::<RefreshRunRecord>(&mut conn)
+                        .optional()
+                        .map_err(DbError::from)
+                }
+
+                fn get_running_refresh_run(&self) -> DbResult<Option<RefreshRunRecord>> {
+                    let mut conn = self.conn()?;
+                    refresh_runs::table
+                        .filter(refresh_runs::status.eq("running"))
+                        .order(refresh_runs::started_at.desc())
+                        .select(RefreshRunRecord::as_select())
+                        .first::<RefreshRunRecord>(&mut conn)
+                        .optional()
+                        .map_err(DbError::from)
+                }
+
+                fn fail_stale_refresh_runs(&self, error: &str) -> DbResult<usize> {
+                    let mut conn = self.conn()?;
+                    diesel::update(refresh_runs::table.filter(refresh_runs::status.eq("running")))
+                        .set((
+                            refresh_runs::status.eq("failed"),
+                            refresh_runs::error.eq(error),
+                            refresh_runs::finished_at.eq(chrono::Utc::now().naive_utc()),
+                        ))
+                        .execute(&mut conn)
+                        .map_err(DbError::from)
+                }
+
+                fn try_acquire_refresh_lock(&self) -> DbResult<Option<RefreshLock>> {
+                    self.acquire_refresh_lock()
+                }
+
+                fn run_pending_migrations(&self) -> DbResult<Vec<String>> {
+                    let mut conn = self.conn()?;
+                    run_embedded_migrations(&mut *conn, MIGRATIONS)
+                }
+
+                fn check_schema_version(&self) -> DbResult<()> {
+                    let mut conn = self.conn()?;
+                    check_embedded_migrations(&mut *conn, MIGRATIONS)
+                }
+            }
+        }
+    };
+}
+pub(crate) use sql_repository;
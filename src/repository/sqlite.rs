@@ -0,0 +1,358 @@
+use diesel::sqlite::SqliteConnection;
+use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use diesel_migrations::{EmbeddedMigrations, embed_migrations};
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool, PooledConnection};
+use diesel::upsert::excluded;
+use crate::config::db::{DbResult, pool_builder};
+use crate::models::models::{Currency as CurrencyModel, UpsertCountry};
+use crate::repository::{sql::sql_repository, RefreshLock};
+use crate::schema::{countries, currencies};
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_sqlite");
+
+sql_repository!(SqliteRepository, Sqlite, true);
+
+type PooledConn = PooledConnection<ConnectionManager<SqliteConnection>>;
+
+#[derive(Debug, Clone)]
+pub struct SqliteRepository {
+    pool: Pool<ConnectionManager<SqliteConnection>>,
+}
+
+/// Lets pooled connections wait on each other's write locks instead of failing with `SQLITE_BUSY`.
+#[derive(Debug)]
+struct SqlitePragmas;
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for SqlitePragmas {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute("PRAGMA busy_timeout = 5000; PRAGMA journal_mode = WAL;")
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+impl SqliteRepository {
+    /// Accepts `sqlite://path/to/file.db`, `sqlite:path/to/file.db` or `sqlite::memory:`.
+    ///
+    /// Every SQLite connection to `:memory:` opens its own empty database, so that pool
+    /// keeps exactly one connection open for the life of the process.
+    pub fn new(database_url: &str) -> Self {
+        let path = database_url
+            .strip_prefix("sqlite://")
+            .or_else(|| database_url.strip_prefix("sqlite:"))
+            .unwrap_or(database_url);
+        let manager = ConnectionManager::<SqliteConnection>::new(path);
+        let mut builder = pool_builder().connection_customizer(Box::new(SqlitePragmas));
+        if path == ":memory:" {
+            builder = builder.max_size(1).min_idle(None).idle_timeout(None).max_lifetime(None);
+        }
+        SqliteRepository { pool: builder.build_unchecked(manager) }
+    }
+
+    fn conn(&self) -> DbResult<PooledConn> {
+        Ok(self.pool.get()?)
+    }
+
+    /// Upserts currency names and symbols, leaving stored rates alone. SQLite gets
+    /// one row per statement since it cannot batch rows with nullable columns.
+    fn upsert_currency_names(conn: &mut PooledConn, rows: &[CurrencyModel]) -> QueryResult<()> {
+        for row in rows {
+            diesel::insert_into(currencies::table)
+                .values(row)
+                .on_conflict(currencies::code)
+                .do_update()
+                .set((
+                    currencies::name.eq(excluded(currencies::name)),
+                    currencies::symbol.eq(excluded(currencies::symbol)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Uses `INSERT ... ON CONFLICT (name) DO UPDATE`, one row per statement.
+    fn upsert_country_rows(conn: &mut PooledConn, rows: &[UpsertCountry]) -> QueryResult<()> {
+        for row in rows {
+            diesel::insert_into(countries::table)
+                .values(row)
+                .on_conflict(countries::name)
+                .do_update()
+                .set((
+                    countries::capital.eq(excluded(countries::capital)),
+                    countries::region.eq(excluded(countries::region)),
+                    countries::population.eq(excluded(countries::population)),
+                    countries::currency_code.eq(excluded(countries::currency_code)),
+                    countries::exchange_rate.eq(excluded(countries::exchange_rate)),
+                    countries::estimated_gdp.eq(excluded(countries::estimated_gdp)),
+                    countries::flag_url.eq(excluded(countries::flag_url)),
+                    countries::last_refreshed_at.eq(excluded(countries::last_refreshed_at)),
+                    countries::alpha2_code.eq(excluded(countries::alpha2_code)),
+                    countries::alpha3_code.eq(excluded(countries::alpha3_code)),
+                    countries::numeric_code.eq(excluded(countries::numeric_code)),
+                    countries::name_key.eq(excluded(countries::name_key)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Upserts the current rate of each currency, one row per statement.
+    fn upsert_rate_rows(conn: &mut PooledConn, rows: &[CurrencyModel]) -> QueryResult<()> {
+        for row in rows {
+            diesel::insert_into(currencies::table)
+                .values(row)
+                .on_conflict(currencies::code)
+                .do_update()
+                .set((
+                    currencies::exchange_rate.eq(excluded(currencies::exchange_rate)),
+                    currencies::rate_updated_at.eq(excluded(currencies::rate_updated_at)),
+                    currencies::rate_base.eq(excluded(currencies::rate_base)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// A SQLite file is only ever served by one process, so the in-process job
+    /// registry already serialises refreshes and no database lock is needed.
+    fn acquire_refresh_lock(&self) -> DbResult<Option<RefreshLock>> {
+        Ok(Some(RefreshLock::new(())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::sql_types::{BigInt, Text};
+    use crate::config::db::DbError;
+    use crate::models::countries_models::Country;
+    use crate::models::models::{CacheMetadata, Country as CountryModel, RefreshRun};
+    use crate::repository::{filter::parse_filter, CountryQuery, CountryRepository, Keyset, SortField, SortKey};
+    use crate::routes::test_support::{country, nigeria, rate};
+
+    fn migrated() -> SqliteRepository {
+        let repo = SqliteRepository::new("sqlite::memory:");
+        repo.run_pending_migrations().unwrap();
+        repo
+    }
+
+    #[derive(QueryableByName)]
+    struct Pragma {
+        #[diesel(sql_type = BigInt)]
+        timeout: i64,
+    }
+
+    #[derive(QueryableByName)]
+    struct JournalMode {
+        #[diesel(sql_type = Text)]
+        journal_mode: String,
+    }
+
+    #[test]
+    fn every_pragma_is_applied_to_pooled_connections() {
+        let path = std::env::temp_dir().join(format!("countries-pragmas-{}.db", std::process::id()));
+        let repo = SqliteRepository::new(&format!("sqlite://{}", path.display()));
+        let mut conn = repo.conn().unwrap();
+
+        let busy = diesel::sql_query("PRAGMA busy_timeout").get_result::<Pragma>(&mut conn).unwrap();
+        let journal = diesel::sql_query("PRAGMA journal_mode").get_result::<JournalMode>(&mut conn).unwrap();
+        drop(conn);
+        drop(repo);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+
+        assert_eq!(busy.timeout, 5000);
+        assert_eq!(journal.journal_mode, "wal");
+    }
+
+    #[test]
+    fn in_memory_database_survives_across_checkouts() {
+        let repo = migrated();
+
+        repo.check_schema_version().unwrap();
+        assert!(repo.get_currencies().unwrap().is_empty());
+    }
+
+    #[test]
+    fn refresh_stores_countries_rates_and_history_together() {
+        let repo = migrated();
+        let processed = repo.upsert_refresh(&[nigeria()], &[rate("NGN", 1460.5), rate("EUR", 0.87)]).unwrap();
+
+        assert_eq!(processed, 1);
+        assert!(repo.get_country_by_name("nigeria").unwrap().is_some());
+        let naira = repo.get_currency("NGN").unwrap().unwrap();
+        assert_eq!(naira.name.as_deref(), Some("Nigerian naira"));
+        assert_eq!(naira.exchange_rate, Some(1460.5));
+        assert_eq!(repo.get_exchange_rate_history("EUR", None, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn failed_refresh_leaves_the_previous_rows() {
+        let repo = migrated();
+        repo.upsert_refresh(&[nigeria()], &[rate("NGN", 1460.5)]).unwrap();
+        diesel::sql_query("CREATE TRIGGER reject_history BEFORE INSERT ON exchange_rates BEGIN SELECT RAISE(ABORT, 'rejected'); END")
+            .execute(&mut repo.conn().unwrap())
+            .unwrap();
+
+        let mut renamed = nigeria();
+        renamed.capital = Some("Lagos".to_string());
+        assert!(repo.upsert_refresh(&[renamed], &[rate("NGN", 1500.0)]).is_err());
+
+        let country = repo.get_country_by_name("Nigeria").unwrap().unwrap();
+        assert_eq!(country.capital.as_deref(), Some("Abuja"));
+        assert_eq!(repo.get_currency("NGN").unwrap().unwrap().exchange_rate, Some(1460.5));
+    }
+
+    #[test]
+    fn filter_on_currency_code_covers_every_currency_of_a_country() {
+        let repo = migrated();
+        let zimbabwe = country(serde_json::json!({
+            "name": "Zimbabwe",
+            "currencies": [{ "code": "ZWL" }, { "code": "USD" }],
+        }));
+        let antarctica = country(serde_json::json!({ "name": "Antarctica" }));
+        repo.upsert_refresh(&[nigeria(), zimbabwe, antarctica], &[]).unwrap();
+
+        let names = |filter: &str| -> Vec<String> {
+            let query = CountryQuery { filter: Some(parse_filter(filter).unwrap()), limit: 10, ..CountryQuery::default() };
+            repo.search_countries(&query).unwrap().countries.into_iter().filter_map(|c| c.name).collect()
+        };
+
+        assert_eq!(names("currency_code = usd"), ["Zimbabwe"]);
+        assert_eq!(names("currency_code != USD"), ["Nigeria"]);
+        assert_eq!(names("not currency_code = USD"), ["Nigeria"]);
+        assert_eq!(names("currency_code in (NGN, ZWL)"), ["Nigeria", "Zimbabwe"]);
+        assert_eq!(names("currency_code not in (NGN)"), ["Zimbabwe"]);
+        assert_eq!(names("currency_code is null"), ["Antarctica"]);
+        assert_eq!(names("currency_code is not null"), ["Nigeria", "Zimbabwe"]);
+
+        let query = CountryQuery { currency_code: Some("usd".to_string()), limit: 10, ..CountryQuery::default() };
+        let by_parameter: Vec<String> = repo.search_countries(&query).unwrap().countries.into_iter().filter_map(|c| c.name).collect();
+        assert_eq!(by_parameter, ["Zimbabwe"]);
+    }
+
+    #[test]
+    fn keyset_pages_follow_the_sort_order_with_nulls_last() {
+        let repo = migrated();
+        let countries: Vec<Country> = [
+            ("Angola", Some("Africa"), Some(10)),
+            ("Benin", Some("Africa"), None),
+            ("Chad", Some("Africa"), Some(30)),
+            ("Estonia", Some("Europe"), Some(5)),
+            ("Finland", Some("Europe"), Some(5)),
+            ("Nowhere", None, Some(7)),
+            ("Void", None, None),
+        ]
+        .into_iter()
+        .map(|(name, region, population)| country(serde_json::json!({ "name": name, "region": region, "population": population })))
+        .collect();
+        repo.upsert_refresh(&countries, &[]).unwrap();
+
+        let sort = vec![
+            SortKey { field: SortField::Region, descending: false },
+            SortKey { field: SortField::Population, descending: true },
+        ];
+        let search = |keyset: Option<Keyset>, limit: i64| {
+            let query = CountryQuery { sort: sort.clone(), keyset, limit, ..CountryQuery::default() };
+            repo.search_countries(&query).unwrap().countries
+        };
+        let names = |rows: &[CountryModel]| rows.iter().filter_map(|c| c.name.clone()).collect::<Vec<_>>();
+
+        let full = search(None, 100);
+        assert_eq!(names(&full), ["Chad", "Angola", "Benin", "Estonia", "Finland", "Nowhere", "Void"]);
+
+        let mut forward = Vec::new();
+        let mut keyset = None;
+        loop {
+            let page = search(keyset, 2);
+            let Some(last) = page.last() else { break };
+            keyset = Some(Keyset::at(last, &sort, false));
+            forward.extend(names(&page));
+        }
+        assert_eq!(forward, names(&full));
+
+        let mut backward = names(&full[full.len() - 1..]);
+        let mut keyset = Some(Keyset::at(full.last().unwrap(), &sort, true));
+        loop {
+            let page = search(keyset, 2);
+            let Some(first) = page.first() else { break };
+            keyset = Some(Keyset::at(first, &sort, true));
+            backward.splice(0..0, names(&page));
+        }
+        assert_eq!(backward, names(&full));
+    }
+
+    #[test]
+    fn latest_summary_metadata_is_the_last_one_saved() {
+        let repo = migrated();
+        assert!(repo.get_latest_summary_metadata().unwrap().is_none());
+
+        let at = |day: u32| chrono::NaiveDate::from_ymd_opt(2025, 11, day).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        repo.save_summary_metadata(CacheMetadata::new(250, &[], at(4))).unwrap();
+        repo.save_summary_metadata(CacheMetadata::new(249, &[nigeria().struct_to_upsert_country()], at(5))).unwrap();
+
+        let latest = repo.get_latest_summary_metadata().unwrap().unwrap();
+        assert_eq!(latest.file_path, "cache/summary.png");
+        assert_eq!(latest.total_countries, 249);
+        assert_eq!(latest.last_refreshed_at, at(5));
+        let top: serde_json::Value = serde_json::from_str(&latest.top_countries_json).unwrap();
+        assert_eq!(top[0]["name"], "Nigeria");
+    }
+
+    #[test]
+    fn stale_running_runs_are_marked_failed() {
+        let repo = migrated();
+        let started_at = chrono::NaiveDate::from_ymd_opt(2025, 11, 5).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        for (job_id, status) in [("crashed", "running"), ("done", "success")] {
+            repo.save_refresh_run(RefreshRun {
+                job_id: job_id.to_string(),
+                triggered_by: "manual".to_string(),
+                status: status.to_string(),
+                countries_processed: 0,
+                error: None,
+                started_at,
+                finished_at: None,
+            }).unwrap();
+        }
+
+        assert_eq!(repo.fail_stale_refresh_runs("interrupted").unwrap(), 1);
+        assert!(repo.get_running_refresh_run().unwrap().is_none());
+        let crashed = repo.get_refresh_run_by_job_id("crashed").unwrap().unwrap();
+        assert_eq!((crashed.status.as_str(), crashed.error.as_deref()), ("failed", Some("interrupted")));
+        assert!(crashed.finished_at.is_some());
+        assert_eq!(repo.get_refresh_run_by_job_id("done").unwrap().unwrap().status, "success");
+    }
+
+    #[test]
+    fn migrations_apply_once_and_revert_cleanly() {
+        let repo = SqliteRepository::new("sqlite::memory:");
+        let applied = repo.run_pending_migrations().unwrap();
+        assert!(!applied.is_empty());
+        assert!(repo.run_pending_migrations().unwrap().is_empty());
+
+        let mut conn = repo.conn().unwrap();
+        let reverted = diesel_migrations::MigrationHarness::revert_all_migrations(&mut *conn, MIGRATIONS).unwrap();
+        assert_eq!(reverted.len(), applied.len());
+        drop(conn);
+        assert_eq!(repo.run_pending_migrations().unwrap(), applied);
+    }
+
+    #[test]
+    fn schema_newer_than_the_binary_is_refused() {
+        let repo = migrated();
+        let mut conn = repo.conn().unwrap();
+        diesel::sql_query("INSERT INTO __diesel_schema_migrations (version) VALUES ('00000000000000')").execute(&mut conn).unwrap();
+        drop(conn);
+        repo.check_schema_version().unwrap();
+
+        let mut conn = repo.conn().unwrap();
+        diesel::sql_query("INSERT INTO __diesel_schema_migrations (version) VALUES ('99991231000000')").execute(&mut conn).unwrap();
+        drop(conn);
+        match repo.check_schema_version() {
+            Err(DbError::Migration(e)) => assert!(e.contains("(99991231000000)"), "{}", e),
+            other => panic!("expected a migration error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use actix_web::{
+    HttpResponse, HttpResponseBuilder, ResponseError,
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{StatusCode, header},
+    middleware::Next,
+};
+use crate::config::db::DbError;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Sent as `Retry-After` with a 503 for an exhausted connection pool.
+const DATABASE_RETRY_AFTER_SECS: u64 = 5;
+
+/// Error type returned by every route. Rendered as the envelope
+/// `{ "status": <code>, "error": <title>, "details": <string or object> }`,
+/// plus `job_id` for refresh conflicts and `suggestions` for name lookups that matched nothing.
+/// Clients sending `Accept: application/problem+json` get the same error as an RFC 9457 problem document instead.
+#[derive(Debug, Clone)]
+pub enum ApiError {
+    Validation(serde_json::Value),
+    NotFound(String, String),
+    /// A 404 for a name lookup, carrying the closest names that do exist.
+    NotFoundWithSuggestions(String, String, Vec<String>),
+    UpstreamUnavailable(String),
+    /// No pooled database connection became free in time; answered with a `Retry-After` header.
+    DatabaseUnavailable(String),
+    Conflict(String, Option<String>),
+    Internal(String),
+}
+
+impl ApiError {
+    /// Validation error for a single field, e.g. `ApiError::invalid("name", "is required")`.
+    pub fn invalid(field: &str, message: &str) -> Self {
+        ApiError::Validation(serde_json::json!({ field: message }))
+    }
+
+    /// Logs the underlying cause and returns an internal error exposing only `context`.
+    pub fn internal(context: &str, cause: impl std::fmt::Display) -> Self {
+        println!("{}: {}", context, cause);
+        ApiError::Internal(context.to_string())
+    }
+
+    fn title(&self) -> String {
+        match self {
+            ApiError::Validation(_) => "Validation failed".to_string(),
+            ApiError::NotFound(title, _) | ApiError::NotFoundWithSuggestions(title, _, _) => title.clone(),
+            ApiError::UpstreamUnavailable(_) => "External data source unavailable".to_string(),
+            ApiError::DatabaseUnavailable(_) => "Database unavailable".to_string(),
+            ApiError::Conflict(_, _) => "Refresh already in progress".to_string(),
+            ApiError::Internal(_) => "Internal Server Error".to_string(),
+        }
+    }
+
+    fn details(&self) -> serde_json::Value {
+        match self {
+            ApiError::Validation(details) => details.clone(),
+            ApiError::NotFound(_, details)
+            | ApiError::NotFoundWithSuggestions(_, details, _)
+            | ApiError::UpstreamUnavailable(details)
+            | ApiError::DatabaseUnavailable(details)
+            | ApiError::Conflict(details, _)
+            | ApiError::Internal(details) => serde_json::json!(details),
+        }
+    }
+
+    /// Members added to the envelope and the problem document beyond the standard ones.
+    fn extensions(&self) -> Vec<(&'static str, serde_json::Value)> {
+        match self {
+            ApiError::Conflict(_, job_id) => vec![("job_id", serde_json::json!(job_id))],
+            ApiError::NotFoundWithSuggestions(_, _, suggestions) => vec![("suggestions", serde_json::json!(suggestions))],
+            _ => Vec::new(),
+        }
+    }
+
+    fn response_builder(&self) -> HttpResponseBuilder {
+        let mut builder = HttpResponse::build(self.status_code());
+        if let ApiError::DatabaseUnavailable(_) = self {
+            builder.insert_header((header::RETRY_AFTER, DATABASE_RETRY_AFTER_SECS.to_string()));
+        }
+        builder
+    }
+
+    pub fn problem_response(&self) -> HttpResponse {
+        let mut problem = serde_json::json!({
+            "type": "about:blank",
+            "title": self.title(),
+            "status": self.status_code().as_u16(),
+            "detail": self.details(),
+        });
+        for (key, value) in self.extensions() {
+            problem[key] = value;
+        }
+
+        self.response_builder()
+            .insert_header((header::CONTENT_TYPE, PROBLEM_JSON))
+            .body(problem.to_string())
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.title(), self.details())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_, _) | ApiError::NotFoundWithSuggestions(_, _, _) => StatusCode::NOT_FOUND,
+            ApiError::UpstreamUnavailable(_) | ApiError::DatabaseUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Conflict(_, _) => StatusCode::CONFLICT,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = serde_json::json!({
+            "status": self.status_code().as_u16(),
+            "error": self.title(),
+            "details": self.details(),
+        });
+        for (key, value) in self.extensions() {
+            body[key] = value;
+        }
+
+        self.response_builder().json(body)
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(e: DbError) -> Self {
+        match e {
+            DbError::Pool(e) => {
+                println!("No database connection available: {}", e);
+                ApiError::DatabaseUnavailable("No database connection is free, try again shortly".to_string())
+            },
+            e => ApiError::internal("Error accessing the database", e),
+        }
+    }
+}
+
+/// Re-renders `ApiError` responses as `application/problem+json` when the client asks for it.
+pub async fn negotiate_problem_json(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let wants_problem = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains(PROBLEM_JSON));
+
+    let res = next.call(req).await?;
+    if !wants_problem {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let problem = res.response().error().and_then(|e| e.as_error::<ApiError>()).map(|e| e.problem_response());
+    match problem {
+        Some(problem) => Ok(res.into_response(problem)),
+        None => Ok(res.map_into_boxed_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use actix_web::{ResponseError, http::{StatusCode, header}};
+    use diesel::r2d2::{ManageConnection, Pool};
+    use crate::config::db::DbError;
+    use super::ApiError;
+
+    // A pool whose connections never open, so `get` times out like an exhausted pool.
+    #[derive(Debug)]
+    struct Unreachable;
+
+    impl ManageConnection for Unreachable {
+        type Connection = ();
+        type Error = std::io::Error;
+
+        fn connect(&self) -> Result<(), std::io::Error> {
+            Err(std::io::Error::other("unreachable"))
+        }
+
+        fn is_valid(&self, _: &mut ()) -> Result<(), std::io::Error> {
+            Ok(())
+        }
+
+        fn has_broken(&self, _: &mut ()) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn pool_timeouts_are_unavailable_with_retry_after() {
+        let pool = Pool::builder().connection_timeout(Duration::from_millis(10)).build_unchecked(Unreachable);
+        let error = ApiError::from(DbError::Pool(pool.get().unwrap_err()));
+
+        for response in [error.error_response(), error.problem_response()] {
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "5");
+        }
+        assert_eq!(ApiError::from(DbError::Blocking("gone".to_string())).status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -1,56 +1,255 @@
-use crate::{AppState, models::countries_models::{Country, Currency}};
-use actix_web::{HttpResponse, Responder, get, post, delete, web, HttpRequest, http::header::ContentType};
+use crate::{AppState, search::suggest_index::SuggestIndex, config::{config::Config, db::DbPool}, routes::{api_error::ApiError, pagination::PageRequest}, jobs::refresh_jobs::{BeginRefreshError, JobHandle, JobPhase}, models::{countries_models::{Country, Currency}, country_names::suggest_names, models::{CacheMetadata, Country as CountryModel, Currency as CurrencyModel, RefreshRun}}, repository::{Bounds, CountryField, CountryQuery, filter::{FilterExpr, parse_filter}, RefreshLock, SortField, SortKey}};
+use actix_web::{HttpResponse, get, post, delete, web, HttpRequest};
 use reqwest::Client;
 use rand::Rng;
 use image::{Rgb, RgbImage};
 use imageproc::drawing::{draw_text_mut};
-use rusttype::{Font, Scale};
-use chrono::Local;
 use std::{fs, io::Error, path::PathBuf};
 use std::path::Path;
 use ab_glyph::{FontArc, PxScale};
 use actix_files::NamedFile;
 
-#[derive(serde::Deserialize, Debug, Clone)]
-enum SearchFilter {
-    region(String),
-    currency(String),
-    sort(SortFilter),
+const DEFAULT_SUGGEST_LIMIT: usize = 10;
+const MAX_SUGGEST_LIMIT: usize = 50;
+
+// `sort=` names, in the order they are listed in validation errors.
+const SORT_FIELDS: [(&str, SortField); 6] = [
+    ("name", SortField::Name),
+    ("population", SortField::Population),
+    ("gdp", SortField::Gdp),
+    ("exchange_rate", SortField::ExchangeRate),
+    ("capital", SortField::Capital),
+    ("region", SortField::Region),
+];
+
+
+// `fields=` names, matching the keys of a country in responses.
+const COUNTRY_FIELDS: [(&str, CountryField); 14] = [
+    ("id", CountryField::Id),
+    ("name", CountryField::Name),
+    ("alpha2_code", CountryField::Alpha2Code),
+    ("alpha3_code", CountryField::Alpha3Code),
+    ("numeric_code", CountryField::NumericCode),
+    ("capital", CountryField::Capital),
+    ("region", CountryField::Region),
+    ("population", CountryField::Population),
+    ("currency_code", CountryField::CurrencyCode),
+    ("currencies", CountryField::Currencies),
+    ("exchange_rate", CountryField::ExchangeRate),
+    ("estimated_gdp", CountryField::EstimatedGdp),
+    ("flag_url", CountryField::FlagUrl),
+    ("last_refreshed_at", CountryField::LastRefreshedAt),
+];
+
+#[derive(serde::Deserialize, Debug)]
+struct FieldsQuery {
+    fields: Option<String>,
+    base: Option<String>,
 }
 
-#[derive(serde::Deserialize, Debug, Clone)]
-enum SortFilter {
-    gdp_desc,
+#[derive(serde::Deserialize, Debug)]
+struct SuggestQuery {
+    q: Option<String>,
+    limit: Option<usize>,
 }
 
+#[derive(Debug, Clone)]
+enum SearchFilter {
+    Region(String),
+    Currency(String),
+    Sort(Vec<SortKey>),
+    Population(Bounds<i64>),
+    Gdp(Bounds<f64>),
+    ExchangeRate(Bounds<f64>),
+    Expression(FilterExpr),
+}
 
 #[derive(serde::Deserialize, Debug)]
 struct SearchQuery {
     region: Option<String>,
     currency: Option<String>,
-    sort: Option<SortFilter>,
+    sort: Option<String>,
+    filter: Option<String>,
+    fields: Option<String>,
+    base: Option<String>,
+    // Parsed by hand so a bad value is reported against its own parameter.
+    population_min: Option<String>,
+    population_max: Option<String>,
+    gdp_min: Option<String>,
+    gdp_max: Option<String>,
+    exchange_rate_min: Option<String>,
+    exchange_rate_max: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    cursor: Option<String>,
+}
+
+
+#[derive(Debug, Clone)]
+pub enum RefreshError {
+    CountriesUnavailable(String),
+    ExchangeRatesUnavailable(String),
+    Database(String),
+    Panicked(String),
+}
+
+impl std::fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RefreshError::CountriesUnavailable(url) => write!(f, "Could not fetch data from {}", url),
+            RefreshError::ExchangeRatesUnavailable(url) => write!(f, "Could not fetch data from {}", url),
+            RefreshError::Database(e) => write!(f, "Error saving countries to database: {}", e),
+            RefreshError::Panicked(e) => write!(f, "Refresh stopped unexpectedly: {}", e),
+        }
+    }
+}
+
+impl From<RefreshError> for ApiError {
+    fn from(e: RefreshError) -> Self {
+        match e {
+            RefreshError::CountriesUnavailable(_) | RefreshError::ExchangeRatesUnavailable(_) => ApiError::UpstreamUnavailable(e.to_string()),
+            RefreshError::Database(_) => ApiError::internal("Error saving countries to database", e),
+            RefreshError::Panicked(_) => ApiError::internal("Refresh stopped unexpectedly", e),
+        }
+    }
 }
 
 
 #[post("/countries/refresh")]
-async fn refresh_countries_data(_data: web::Data<AppState>) -> impl Responder {
-    let countries_url = &_data.env.countries_api_url;
-    let exchange_rate_url = &_data.env.exchange_rate_api_url;
+async fn refresh_countries_data(_data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let (job, lock) = match _data.jobs.begin(&_data.env.db, "manual").await {
+        Ok(started) => started,
+        Err(BeginRefreshError::AlreadyRunning(running_id)) => {
+            return Err(ApiError::Conflict("Another refresh is currently running, try again once it finishes".to_string(), running_id));
+        },
+        Err(BeginRefreshError::Database(e)) => {
+            return Err(ApiError::internal("Error starting refresh job", e));
+        }
+    };
+    let job_id = job.id.clone();
+    let env = _data.env.clone();
+    let suggest = _data.suggest.clone();
+
+    actix_web::rt::spawn(async move {
+        let _ = run_refresh_job(&env, &suggest, job, lock).await;
+    });
+
+    let json_response = serde_json::json!({
+        "status": "accepted",
+        "job_id": job_id,
+        "status_url": format!("/api/v1/jobs/{}", job_id),
+    });
+    Ok(HttpResponse::Accepted().json(json_response))
+}
+
+
+/// Runs the refresh pipeline for `job`, then records the outcome on the job and in `refresh_runs`
+/// and rebuilds the suggest index from the refreshed rows.
+/// `lock` is released once the outcome has been recorded.
+pub async fn run_refresh_job(env: &Config, suggest: &SuggestIndex, job: JobHandle, lock: RefreshLock) -> Result<usize, RefreshError> {
+    let pipeline_env = env.clone();
+    let pipeline_job = job.clone();
+    let result = catch_panic(async move { run_refresh_pipeline(&pipeline_env, &pipeline_job).await }).await;
+
+    if result.is_ok() {
+        match suggest.reload(&env.db).await {
+            Ok(count) => println!("Suggest index rebuilt with {} countries", count),
+            Err(e) => println!("Error rebuilding suggest index: {}", e),
+        }
+    }
+
+    match &result {
+        Ok(count) => {
+            println!("Refresh job {} completed: {} countries processed", job.id, count);
+            job.complete(serde_json::json!({
+                "message": "Database Updated Succesfully",
+                "countries_processed": count,
+            }));
+        },
+        Err(e) => {
+            println!("Refresh job {} failed: {}", job.id, e);
+            job.fail(e.to_string());
+        }
+    }
+
+    if let Some(snapshot) = job.snapshot() {
+        let run = RefreshRun {
+            job_id: snapshot.id,
+            triggered_by: snapshot.triggered_by,
+            status: if result.is_ok() { "success".to_string() } else { "failed".to_string() },
+            countries_processed: snapshot.countries_processed as i32,
+            error: if snapshot.errors.is_empty() { None } else { Some(snapshot.errors.join("; ")) },
+            started_at: snapshot.started_at,
+            finished_at: Some(snapshot.finished_at.unwrap_or_else(|| chrono::Utc::now().naive_utc())),
+        };
+        if let Err(e) = env.db.run(move |db| db.finish_refresh_run(run)).await {
+            println!("Error saving refresh run outcome: {}", e);
+        }
+    }
+
+    drop(lock);
+    result
+}
+
+
+/// Runs `pipeline` on its own task, so a panic inside it is reported as a failed refresh
+/// instead of unwinding past the code that finishes the job.
+async fn catch_panic<F>(pipeline: F) -> Result<usize, RefreshError>
+where
+    F: std::future::Future<Output = Result<usize, RefreshError>> + 'static,
+{
+    match actix_web::rt::spawn(pipeline).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => {
+            let panic = e.into_panic();
+            let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic".to_string());
+            Err(RefreshError::Panicked(message))
+        },
+        Err(e) => Err(RefreshError::Panicked(e.to_string())),
+    }
+}
+
+
+/// Fetch → rate → GDP → upsert → image. Returns the number of countries written.
+pub async fn run_refresh_pipeline(env: &Config, job: &JobHandle) -> Result<usize, RefreshError> {
+    let countries_url = &env.countries_api_url;
+    let exchange_rate_url = &env.exchange_rate_api_url;
     let last_refreshed_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
-    let db = &_data.env.db;
-    let mut countries_4_image = Vec::new();
+    let db = &env.db;
+    let mut refreshed: Vec<Country> = Vec::new();
+    let mut rate_table: Vec<CurrencyModel> = Vec::new();
 
+    job.set_phase(JobPhase::Fetching);
     println!("Countries API URL: {}", countries_url);
     println!("Exchange Rate API URL: {}", exchange_rate_url);
 
     match get_exchange_rates(exchange_rate_url).await {
-        Ok(rates) => {
+        Ok((rates, base_code)) => {
+            let rates_refreshed_at = chrono::NaiveDateTime::parse_from_str(&last_refreshed_at, "%Y-%m-%dT%H:%M:%S%.fZ").ok();
+            if let Some(table) = rates.as_object() {
+                rate_table = table.iter()
+                    .filter_map(|(code, rate)| Some(CurrencyModel {
+                        code: code.clone(),
+                        name: None,
+                        symbol: None,
+                        exchange_rate: Some(rate.as_f64()?),
+                        rate_updated_at: rates_refreshed_at,
+                        rate_base: base_code.clone(),
+                    }))
+                    .collect();
+            }
+
             match fetch_countries_data(countries_url).await {
                 Ok(countries) => {
                     println!("Fetched {} countries", countries.len());
-                    countries_4_image = countries.clone();
 
                     for mut country in countries.into_iter() {
+                        for currency in country.currencies.iter_mut() {
+                            currency.exchange_rate = currency.code.as_ref().and_then(|c| rates.get(c)).and_then(|r| r.as_f64());
+                        }
+
                         let code = country.get_currency_code_owned();
                         if let Some(c_code) = code {
                             match rates.get(&c_code) {
@@ -59,7 +258,12 @@ async fn refresh_countries_data(_data: web::Data<AppState>) -> impl Responder {
                                     println!("Exchange rate for {}: {}, with random no: {}", c_code, rate, random_no);
                                     country.set_exchange_rate(rate.as_f64().unwrap_or(0.0));
                                     let estimated_gdp = (country.population.unwrap_or(0) as f64 * random_no as f64) / country.exchange_rate.unwrap_or(0.0);
-                                    country.set_estimated_gdp(estimated_gdp);
+                                    // A zero rate would store inf or NaN, which MySQL rejects and no sort order can rank sensibly.
+                                    if estimated_gdp.is_finite() {
+                                        country.set_estimated_gdp(estimated_gdp);
+                                    } else {
+                                        country.estimated_gdp = None;
+                                    }
                                 },
                                 None => {
                                     country.estimated_gdp = None;
@@ -75,34 +279,56 @@ async fn refresh_countries_data(_data: web::Data<AppState>) -> impl Responder {
                         
                         country.set_last_refreshed_at(last_refreshed_at.clone());
                         println!("Country: {:?}", country);
-                        db.insert_or_update_country(country).unwrap();
+                        refreshed.push(country);
                     }
                 },
                 Err(e) => {
                     println!("Error fetching countries data: {}", e);
-                    let json_response = serde_json::json!({
-                        "error": "External data source unavailable",
-                        "details": format!("Could not fetch data from {}", countries_url),
-                    });
-                    return HttpResponse::ServiceUnavailable().json(json_response);
+                    return Err(RefreshError::CountriesUnavailable(countries_url.clone()));
                 }
             }
         },
         Err(e) => {
             println!("Error fetching initial exchange rates: {}", e);
-            let json_response = serde_json::json!({
-                "error": "External data source unavailable",
-                "details": format!("Could not fetch data from {}", exchange_rate_url),
-            });
-            return HttpResponse::ServiceUnavailable().json(json_response);
+            return Err(RefreshError::ExchangeRatesUnavailable(exchange_rate_url.clone()));
         }
     }
 
-    match generate_summary_image(countries_4_image, last_refreshed_at) {
-        Ok(_) => println!("Summary image generated successfully"),
-        Err(e) => println!("Error generating summary image: {}", e),
+    job.set_phase(JobPhase::Upserting);
+    let to_upsert = refreshed.clone();
+    // The whole upstream rate table goes in too, including currencies that no country uses.
+    let processed = match db.run(move |db| db.upsert_refresh(&to_upsert, &rate_table)).await {
+        Ok(count) => count,
+        Err(e) => return Err(RefreshError::Database(e.to_string())),
+    };
+    job.set_countries_processed(processed);
+
+    job.set_phase(JobPhase::Rendering);
+    let metadata = chrono::NaiveDateTime::parse_from_str(&last_refreshed_at, "%Y-%m-%dT%H:%M:%S%.fZ")
+        .ok()
+        .map(|at| {
+            let top = top_by_estimated_gdp(&refreshed);
+            let rows = top.iter().map(|c| c.struct_to_upsert_country()).collect::<Vec<_>>();
+            CacheMetadata::new(refreshed.len(), &rows, at)
+        });
+    let rendered = web::block(move || generate_summary_image(refreshed, last_refreshed_at))
+        .await
+        .unwrap_or_else(|e| Err(Error::other(e.to_string())));
+    match rendered {
+        Ok(_) => {
+            println!("Summary image generated successfully");
+            if let Some(metadata) = metadata
+                && let Err(e) = db.run(move |db| db.save_summary_metadata(metadata)).await {
+                println!("Error saving summary metadata: {}", e);
+                job.push_error(format!("Error saving summary metadata: {}", e));
+            }
+        },
+        Err(e) => {
+            println!("Error generating summary image: {}", e);
+            job.push_error(format!("Error generating summary image: {}", e));
+        }
     }
-    HttpResponse::Ok().json(" Database Updated Succesfully" )
+    Ok(processed)
 }
 
 
@@ -110,7 +336,6 @@ async fn fetch_countries_data(country_url: &str) -> Result<Vec<Country>, String>
     let client = Client::new();
 
     let mut countries_response: Vec<Country> = Vec::new();
-    let mut countries_error: String = String::new();
 
     match client.get(country_url).send().await {
         Ok(resp) => match resp.json::<serde_json::Value>().await {
@@ -121,333 +346,550 @@ async fn fetch_countries_data(country_url: &str) -> Result<Vec<Country>, String>
                     countries_response.push(country_data);
                 }
             }
-            Err(_) =>{ println!("Failed to parse Countries JSON response"); countries_error = "Failed to parse Countries JSON response".to_string(); return Err(countries_error) }
+            Err(_) =>{ println!("Failed to parse Countries JSON response"); return Err("Failed to parse Countries JSON response".to_string()) }
         },
-        Err(e) =>{ println!("Failed to fetch countries data {:?}", e); countries_error = "Failed to fetch countries data".to_string() ; return Err(countries_error) }
+        Err(e) =>{ println!("Failed to fetch countries data {:?}", e); return Err("Failed to fetch countries data".to_string()) }
     };
     Ok(countries_response)
 }
 
-async fn get_exchange_rates(exchange_rate_url: &str) -> Result<serde_json::Value, String> {
+/// Returns the `rates` object and the `base_code` they are quoted against.
+async fn get_exchange_rates(exchange_rate_url: &str) -> Result<(serde_json::Value, Option<String>), String> {
     let client = Client::new();
 
-    let mut exchange_rate_error: String = String::new();
-
     match client.get(exchange_rate_url).send().await {
         Ok(resp) => match resp.json::<serde_json::Value>().await {
             Ok(json) => {
                 match json.get("rates") {
                     Some(rates) => {
-                        return Ok(rates.clone());
+                        let base_code = json.get("base_code").and_then(|v| v.as_str()).map(|s| s.to_string());
+                        Ok((rates.clone(), base_code))
                     },
                     None => {
                         println!("No 'rates' field found in exchange rate response");
-                        return Err("No 'rates' field found in exchange rate response".to_string());
+                        Err("No 'rates' field found in exchange rate response".to_string())
                     }
                 }
             }
-            Err(_) =>{ println!("Failed to parse Exchange Rate JSON response"); exchange_rate_error = "Failed to parse Exchange Rate JSON response".to_string(); return Err(exchange_rate_error) }
+            Err(_) =>{ println!("Failed to parse Exchange Rate JSON response"); Err("Failed to parse Exchange Rate JSON response".to_string()) }
         },
-        Err(e) =>{ println!("Failed to fetch exchange rate data {:?}", e); exchange_rate_error = "Failed to fetch exchange rate data".to_string(); return Err(exchange_rate_error) }
-    };
+        Err(e) =>{ println!("Failed to fetch exchange rate data {:?}", e); Err("Failed to fetch exchange rate data".to_string()) }
+    }
 }
 
 #[get("/countries")]
-async fn get_countries_data(_data: web::Data<AppState>, query: web::Query<SearchQuery>) -> impl Responder {
+async fn get_countries_data(req: HttpRequest, _data: web::Data<AppState>, query: web::Query<SearchQuery>) -> Result<HttpResponse, ApiError> {
     let q = query.into_inner();
     println!("Search Query: {:?}", q);
 
-    let selected_filters: Vec<SearchFilter> = extract_filters_from_query(&q);
-    let filtered_countries = apply_filters(_data, selected_filters.clone());
+    let mut page = PageRequest::from_request(&req, q.limit, q.offset, q.cursor.as_deref())?;
+    let selected_filters: Vec<SearchFilter> = extract_filters_from_query(&q)?;
+    let fields = parse_fields(q.fields.as_deref())?;
+    let mut country_query = build_country_query(selected_filters, &page);
+    // Cursors are built from the sort values of the page's edge rows, so those are always loaded.
+    country_query.fields = fields.clone().map(|mut selected| {
+        selected.extend(country_query.sort.iter().map(|key| key.field.field()));
+        selected
+    });
+    let db = &_data.env.db;
+    let base = resolve_base(db, q.base.as_deref()).await?;
 
-    match filtered_countries {
-        Ok(mut countries) => {
-            let mut return_countries = serde_json::json!([]);
-            for (index, country) in countries.iter_mut().enumerate() {
-                let country_json = country.structure_country_for_return(index + 1);
-                return_countries.as_array_mut().unwrap().push(country_json);
-            }
-            return HttpResponse::Ok().json(return_countries);
+    let search = country_query.clone();
+    let sort = country_query.sort.clone();
+    let mut result = db.run(move |db| db.search_countries(&search)).await?;
+    if result.total == 0 && let Some(e) = empty_search_error(db, country_query).await? {
+        return Err(e);
+    }
+
+    page.take_page(&mut result.countries, &sort);
+    let return_countries = countries_for_return(db, result.countries, &fields, base.as_ref()).await?;
+    Ok(page.respond(&req, result.total as usize, return_countries))
+}
+
+fn extract_filters_from_query(query: &SearchQuery) -> Result<Vec<SearchFilter>, ApiError> {
+    let mut filters = Vec::new();
+    let mut errors = serde_json::Map::new();
+
+    if let Some(region) = &query.region {
+        filters.push(SearchFilter::Region(region.clone()));
+    }
+
+    if let Some(currency) = &query.currency {
+        filters.push(SearchFilter::Currency(currency.clone()));
+    }
+
+    if let Some(sort) = &query.sort {
+        filters.push(SearchFilter::Sort(parse_sort(sort)?));
+    }
+
+    if let Some(expression) = &query.filter {
+        let parsed = parse_filter(expression).map_err(|e| ApiError::invalid("filter", &e.to_string()))?;
+        filters.push(SearchFilter::Expression(parsed));
+    }
+
+    let population = parse_bounds("population", &query.population_min, &query.population_max, parse_count, &mut errors);
+    let gdp = parse_bounds("gdp", &query.gdp_min, &query.gdp_max, parse_amount, &mut errors);
+    let exchange_rate = parse_bounds("exchange_rate", &query.exchange_rate_min, &query.exchange_rate_max, parse_amount, &mut errors);
+
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(serde_json::Value::Object(errors)));
+    }
+    if let Some(bounds) = population {
+        filters.push(SearchFilter::Population(bounds));
+    }
+    if let Some(bounds) = gdp {
+        filters.push(SearchFilter::Gdp(bounds));
+    }
+    if let Some(bounds) = exchange_rate {
+        filters.push(SearchFilter::ExchangeRate(bounds));
+    }
+
+    Ok(filters)
+}
+
+/// Parses `<name>_min` / `<name>_max` into inclusive bounds, recording every problem in `errors`.
+/// Returns `None` when neither parameter was given or either is invalid.
+fn parse_bounds<T: PartialOrd + Copy>(
+    name: &str,
+    min: &Option<String>,
+    max: &Option<String>,
+    parse: fn(&str) -> Option<T>,
+    errors: &mut serde_json::Map<String, serde_json::Value>,
+) -> Option<Bounds<T>> {
+    if min.is_none() && max.is_none() {
+        return None;
+    }
+
+    let mut bound = |suffix: &str, raw: &Option<String>| -> Result<Option<T>, ()> {
+        match raw.as_deref().map(str::trim) {
+            None => Ok(None),
+            Some(value) => parse(value).map(Some).ok_or_else(|| {
+                errors.insert(format!("{}_{}", name, suffix), serde_json::json!("must be a non-negative number"));
+            }),
+        }
+    };
+    let (min, max) = (bound("min", min), bound("max", max));
+
+    match (min, max) {
+        (Ok(Some(lower)), Ok(Some(upper))) if lower > upper => {
+            errors.insert(format!("{}_min", name), serde_json::json!(format!("must not be greater than {}_max", name)));
+            None
         },
-        Err(e) => {
-            let reason = serde_json::from_str(&e).unwrap_or(serde_json::json!({ "unknown": "error" }));
-            let json_response = serde_json::json!({
-                "error": "Validation failed",
-                "details": reason,
-            });
-            return HttpResponse::BadRequest().json(json_response);
+        (Ok(min), Ok(max)) => Some(Bounds { min, max }),
+        _ => None,
+    }
+}
+
+fn parse_count(value: &str) -> Option<i64> {
+    value.parse::<i64>().ok().filter(|v| *v >= 0)
+}
+
+fn parse_amount(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0)
+}
+
+fn build_country_query(filters: Vec<SearchFilter>, page: &PageRequest) -> CountryQuery {
+    let mut query = CountryQuery {
+        limit: page.fetch_limit() as i64,
+        offset: page.offset as i64,
+        keyset: page.keyset.clone(),
+        ..CountryQuery::default()
+    };
+
+    for filter in filters.into_iter() {
+        match filter {
+            SearchFilter::Region(region_name) => query.region = Some(region_name),
+            SearchFilter::Currency(currency_code) => query.currency_code = Some(currency_code),
+            SearchFilter::Sort(keys) => query.sort = keys,
+            SearchFilter::Population(bounds) => query.population = bounds,
+            SearchFilter::Gdp(bounds) => query.estimated_gdp = bounds,
+            SearchFilter::ExchangeRate(bounds) => query.exchange_rate = bounds,
+            SearchFilter::Expression(expression) => query.filter = Some(expression),
         }
     }
-    
+
+    query
 }
 
-fn apply_filters(_data: web::Data<AppState>, filters: Vec<SearchFilter>) -> Result<Vec<Country>, String> {
-    println!("Applying filters: {:?}", filters);
-    match get_all_countries(_data) {
-        Ok(countries) => {
-            let mut filtered_countries = countries;
-
-            for filter in filters.into_iter() {
-                match filter {
-                    SearchFilter::region(region_name) => {
-                        filtered_countries = filtered_countries.into_iter()
-                            .filter(|c| c.region.as_ref().map_or(false, |r| r.to_lowercase() == region_name.to_lowercase()))
-                            .collect();
-                        if filtered_countries.is_empty() {
-                            let details = serde_json::json!({
-                                "region": "is required"
-                            });
-                            return Err(format!("{}", details));
-                        }
-                    },
-                    SearchFilter::currency(currency_code) => {
-                        filtered_countries = filtered_countries.into_iter()
-                            .filter(|c| {
-                                if let Some(curr) = &c.currencies {
-                                    if let Some(code) = &curr.code {
-                                        return code == &currency_code;
-                                    }
-                                }
-                                false
-                            })
-                            .collect();
-                        if filtered_countries.is_empty() {
-                            let details = serde_json::json!({
-                                "currency_code": "is required"
-                            });
-                            return Err(format!("{}", details));
-                        }
-                    },
-                    SearchFilter::sort(sort_filter) => {
-                        match sort_filter {
-                            SortFilter::gdp_desc => {
-                                filtered_countries.sort_by(|a, b| b.estimated_gdp.partial_cmp(&a.estimated_gdp).unwrap());
-                            },
-                        }
-                    },
-                }
-            }
+/// Parses `fields=name,population`. `None` when the parameter is absent, meaning every field.
+fn parse_fields(fields: Option<&str>) -> Result<Option<Vec<CountryField>>, ApiError> {
+    let Some(fields) = fields else {
+        return Ok(None);
+    };
 
-            return Ok(filtered_countries);
-        },
-        Err(e) => {
-            println!("Error fetching all countries: {}", e);
-            return Err("Error fetching all countries from db".to_string());
+    let mut selected: Vec<CountryField> = Vec::new();
+    for name in fields.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        match COUNTRY_FIELDS.iter().find(|(field_name, _)| *field_name == name) {
+            Some((_, field)) if !selected.contains(field) => selected.push(*field),
+            Some(_) => {},
+            None => {
+                let expected: Vec<&str> = COUNTRY_FIELDS.iter().map(|(field_name, _)| *field_name).collect();
+                return Err(ApiError::invalid("fields", &format!("unknown field '{}', expected one of {}", name, expected.join(", "))));
+            }
         }
     }
+
+    if selected.is_empty() {
+        return Err(ApiError::invalid("fields", "must name at least one field"));
+    }
+    Ok(Some(selected))
+}
+
+/// Drops every key of a country response that `fields` did not ask for.
+fn project(mut country_json: serde_json::Value, fields: &Option<Vec<CountryField>>) -> serde_json::Value {
+    if let (Some(fields), Some(object)) = (fields, country_json.as_object_mut()) {
+        object.retain(|key, _| {
+            COUNTRY_FIELDS.iter().any(|(field_name, field)| field_name == key && fields.contains(field))
+        });
+    }
+    country_json
 }
 
-fn get_all_countries(_data: web::Data<AppState>) -> Result<Vec<Country>, String> {
-    match _data.env.db.get_all_countries() {
-        Ok(countries) => {
-            if countries.is_empty() {
-                return Err("No countries found in database".to_string());
+/// Parses `sort=region,-population`: comma-separated fields, `-` for descending.
+/// The older `<field>_desc` / `<field>_asc` spelling (e.g. `gdp_desc`) is still accepted.
+fn parse_sort(sort: &str) -> Result<Vec<SortKey>, ApiError> {
+    let mut keys: Vec<SortKey> = Vec::new();
+
+    for part in sort.split(',').map(str::trim) {
+        let (name, descending) = if let Some(name) = part.strip_prefix('-') {
+            (name, true)
+        } else if let Some(name) = part.strip_suffix("_desc") {
+            (name, true)
+        } else if let Some(name) = part.strip_suffix("_asc") {
+            (name, false)
+        } else {
+            (part.strip_prefix('+').unwrap_or(part), false)
+        };
+
+        let field = match SORT_FIELDS.iter().find(|(field_name, _)| *field_name == name) {
+            Some((_, field)) => *field,
+            None => {
+                let expected: Vec<&str> = SORT_FIELDS.iter().map(|(field_name, _)| *field_name).collect();
+                return Err(ApiError::invalid("sort", &format!("unknown field '{}', expected one of {}", part, expected.join(", "))));
             }
-            // println!("countries fetched from db are: {:?}", countries);
-            let countries_vec = countries.into_iter().map(|c| {
-                Country {
-                    name: c.name,
-                    capital: c.capital,
-                    region: c.region,
-                    population: c.population.map(|p| p as u64),
-                    flag_url: c.flag_url,
-                    currencies: Some(Currency {
-                        code: c.currency_code,
-                        name: None,
-                        symbol: None,
-                    }),
-                    independent: None,
-                    last_refreshed_at: c.last_refreshed_at.map(|dt| dt.to_string()),
-                    exchange_rate: c.exchange_rate,
-                    estimated_gdp: c.estimated_gdp,
-                }
-            }).collect();
-            return Ok(countries_vec);
-        },
-        Err(e) => {
-            println!("Error retrieving countries from database: {}", e);
-            return Err("Error retrieving countries from database".to_string());
+        };
+        if keys.iter().any(|key| key.field == field) {
+            return Err(ApiError::invalid("sort", &format!("field '{}' is listed more than once", name)));
         }
+        keys.push(SortKey { field, descending });
+    }
+
+    Ok(keys)
+}
+
+/// Explains a search with no matches: an empty table, or the first of `region` then `currency`
+/// that matches nothing on its own. Returns `None` when only the range or `filter=` filters emptied the result,
+/// which is a legitimate empty page.
+async fn empty_search_error(db: &DbPool, query: CountryQuery) -> Result<Option<ApiError>, ApiError> {
+    let unfiltered = query.region.is_none()
+        && query.currency_code.is_none()
+        && query.population == Bounds::default()
+        && query.estimated_gdp == Bounds::default()
+        && query.exchange_rate == Bounds::default()
+        && query.filter.is_none();
+    if unfiltered {
+        return Ok(Some(ApiError::NotFound(
+            "No countries found in database".to_string(),
+            "Countries data might not have been refreshed yet".to_string(),
+        )));
     }
+
+    let region_only = CountryQuery { region: query.region.clone(), limit: 1, ..CountryQuery::default() };
+    let with_currency = CountryQuery { currency_code: query.currency_code.clone(), ..region_only.clone() };
+
+    if query.region.is_some() && db.run(move |db| db.search_countries(&region_only)).await?.total == 0 {
+        return Ok(Some(ApiError::invalid("region", "is required")));
+    }
+    if query.currency_code.is_some() && db.run(move |db| db.search_countries(&with_currency)).await?.total == 0 {
+        return Ok(Some(ApiError::invalid("currency_code", "is required")));
+    }
+    Ok(None)
 }
 
+/// Response bodies for `rows`, each listing every currency its country uses.
+/// The `country_currencies` join is only read when `fields` asks for currencies or a rate-derived field.
+///
+/// Rates and GDP are stored against the base of the last rate table. With `base`, they are re-expressed
+/// against that currency instead; a country whose rates were quoted against another base gets `null`.
+/// Either way the base in force is stated as `base_currency`.
+async fn countries_for_return(
+    db: &DbPool,
+    rows: Vec<CountryModel>,
+    fields: &Option<Vec<CountryField>>,
+    base: Option<&CurrencyModel>,
+) -> Result<Vec<serde_json::Value>, ApiError> {
+    let wants_currencies = fields.as_ref().is_none_or(|f| {
+        f.iter().any(|field| matches!(field, CountryField::Currencies | CountryField::ExchangeRate | CountryField::EstimatedGdp))
+    });
+    let currencies: Vec<(i32, CurrencyModel)> = if wants_currencies {
+        let ids: Vec<i32> = rows.iter().map(|c| c.id).collect();
+        db.run(move |db| db.get_country_currencies(&ids)).await?
+    } else {
+        Vec::new()
+    };
+
+    Ok(rows.into_iter()
+        .map(|c| {
+            let id = c.id;
+            let joined: Vec<&CurrencyModel> = currencies.iter()
+                .filter(|(country_id, _)| *country_id == id)
+                .map(|(_, currency)| currency)
+                .collect();
+            // The country's own rate and GDP were computed from its primary currency's row.
+            let stored_base = joined.first().and_then(|currency| currency.rate_base.clone());
+            let used = joined.iter()
+                .map(|currency| Currency {
+                    code: Some(currency.code.clone()),
+                    name: currency.name.clone(),
+                    symbol: currency.symbol.clone(),
+                    exchange_rate: match base {
+                        Some(base) => rebase_factor(currency.rate_base.as_deref(), base).and_then(|f| currency.exchange_rate.map(|r| r / f)),
+                        None => currency.exchange_rate,
+                    },
+                })
+                .collect();
 
+            let mut country = country_from_row(c, used);
+            if let Some(base) = base {
+                let factor = rebase_factor(stored_base.as_deref(), base);
+                country.exchange_rate = factor.and_then(|f| country.exchange_rate.map(|r| r / f));
+                country.estimated_gdp = factor.and_then(|f| country.estimated_gdp.map(|gdp| gdp * f));
+            }
 
-fn extract_filters_from_query(query: &SearchQuery) -> Vec<SearchFilter> {
-    let mut filters = Vec::new();
+            let mut country_json = project(country.structure_country_for_return(id), fields);
+            if let Some(object) = country_json.as_object_mut()
+                && ["currencies", "exchange_rate", "estimated_gdp"].iter().any(|key| object.contains_key(*key))
+            {
+                let base_currency = base.map(|b| Some(b.code.clone())).unwrap_or(stored_base);
+                object.insert("base_currency".to_string(), serde_json::json!(base_currency));
+            }
+            country_json
+        })
+        .collect())
+}
 
-    if let Some(region) = &query.region {
-        filters.push(SearchFilter::region(region.clone()));
+/// The stored rate of `base`, which converts amounts quoted against `stored_base` into amounts quoted
+/// against `base`: rates divide by it and base-denominated values such as GDP multiply by it.
+/// `None` when the two were not quoted against the same table.
+fn rebase_factor(stored_base: Option<&str>, base: &CurrencyModel) -> Option<f64> {
+    if stored_base.is_none() || base.rate_base.as_deref() != stored_base {
+        return None;
     }
+    base.exchange_rate.filter(|rate| *rate > 0.0)
+}
 
-    if let Some(currency) = &query.currency {
-        filters.push(SearchFilter::currency(currency.clone()));
+/// Loads the currency named by `?base=`, which must be stored with a rate.
+async fn resolve_base(db: &DbPool, base: Option<&str>) -> Result<Option<CurrencyModel>, ApiError> {
+    let Some(code) = base.map(|b| b.trim().to_ascii_uppercase()).filter(|b| !b.is_empty()) else {
+        return Ok(None);
+    };
+    let lookup_code = code.clone();
+    match db.run(move |db| db.get_currency(&lookup_code)).await? {
+        Some(currency) if currency.exchange_rate.is_some_and(|rate| rate > 0.0) => Ok(Some(currency)),
+        Some(_) => Err(ApiError::invalid("base", &format!("no exchange rate is stored for {}", code))),
+        None => Err(ApiError::invalid("base", &format!("unknown currency code '{}'", code))),
     }
+}
 
-    if let Some(sort) = &query.sort {
-        filters.push(SearchFilter::sort(sort.clone()));
+fn country_from_row(c: CountryModel, mut currencies: Vec<Currency>) -> Country {
+    // Rows refreshed before currencies had their own table only know the primary one.
+    if currencies.is_empty() && c.currency_code.is_some() {
+        currencies.push(Currency {
+            code: c.currency_code.clone(),
+            name: None,
+            symbol: None,
+            exchange_rate: c.exchange_rate,
+        });
     }
 
-    filters
+    Country {
+        name: c.name,
+        capital: c.capital,
+        region: c.region,
+        population: c.population.map(|p| p as u64),
+        flag_url: c.flag_url,
+        currencies,
+        last_refreshed_at: c.last_refreshed_at.map(|dt| dt.to_string()),
+        exchange_rate: c.exchange_rate,
+        estimated_gdp: c.estimated_gdp,
+        alpha2_code: c.alpha2_code,
+        alpha3_code: c.alpha3_code,
+        numeric_code: c.numeric_code,
+        alt_spellings: Vec::new(),
+    }
 }
 
 
-#[get("/countries/{name}")]
-async fn get_country_by_name(_data: web::Data<AppState>, path: web::Path<String>) -> impl Responder {
+/// Autocomplete over country names, capitals and ISO codes, answered from the in-memory [`SuggestIndex`].
+#[get("/countries/suggest")]
+async fn suggest_countries(_data: web::Data<AppState>, query: web::Query<SuggestQuery>) -> Result<HttpResponse, ApiError> {
+    let q = query.q.as_deref().unwrap_or_default();
+    if q.trim().is_empty() {
+        return Err(ApiError::invalid("q", "is required"));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_SUGGEST_LIMIT);
+    if limit == 0 || limit > MAX_SUGGEST_LIMIT {
+        return Err(ApiError::invalid("limit", &format!("must be between 1 and {}", MAX_SUGGEST_LIMIT)));
+    }
+
+    let data = _data.suggest.suggest(q, limit);
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "query": q, "data": data })))
+}
+
+
+/// Resolves `{key}` as an exact country name first, then as an ISO 3166 alpha-2, alpha-3 or numeric code.
+#[get("/countries/{key}")]
+async fn get_country_by_name(_data: web::Data<AppState>, path: web::Path<String>, query: web::Query<FieldsQuery>) -> Result<HttpResponse, ApiError> {
     let input_value: String = path.into_inner();
     println!("Received get input for details: {}", input_value);
+    let fields = parse_fields(query.fields.as_deref())?;
 
     if input_value.trim().is_empty() {
-        let json_response = serde_json::json!({
-            "error": "Validation failed",
-            "details": {
-                "name": "is required"
-            }
-        });
-        return HttpResponse::BadRequest().json(json_response);
+        return Err(ApiError::invalid("name", "is required"));
     }
 
     let db = &_data.env.db;
-    match db.get_country_by_name(&input_value) {
-        Ok(country_opt) => {
-            if let Some(c) = country_opt {
-                let country_data =  Country {
-                    name: c.name,
-                    capital: c.capital,
-                    region: c.region,
-                    population: c.population.map(|p| p as u64),
-                    flag_url: c.flag_url,
-                    currencies: Some(Currency {
-                        code: c.currency_code,
-                        name: None,
-                        symbol: None,
-                    }),
-                    independent: None,
-                    last_refreshed_at: c.last_refreshed_at.map(|dt| dt.to_string()),
-                    exchange_rate: c.exchange_rate,
-                    estimated_gdp: c.estimated_gdp,
-                };
-                let country_json = country_data.structure_country_for_return(1);
-                return HttpResponse::Ok().json(country_json);
-            } else {
-                let json_response = serde_json::json!({
-                    "error": "Country not found",
-                    "details": format!("No country found with name: {}", input_value)
-                });
-                return HttpResponse::NotFound().json(json_response);
-            }
-        },
-        Err(e) => {
-            println!("Error retrieving country from database: {}", e);
-            let json_response = serde_json::json!({
-                "error": "Internal Server Error",
-                "details": "Error retrieving country from database"
-            });
-            return HttpResponse::InternalServerError().json(json_response);
+    let base = resolve_base(db, query.base.as_deref()).await?;
+    let lookup_key = input_value.clone();
+    let found = db.run(move |db| {
+        if let Some(c) = db.get_country_by_name(&lookup_key)? {
+            return Ok(Some(c));
+        }
+        match iso_code(&lookup_key) {
+            Some(code) => db.get_country_by_code(&code),
+            None => Ok(None),
         }
+    }).await?;
+
+    match found {
+        Some(c) => {
+            let country_json = countries_for_return(db, vec![c], &fields, base.as_ref()).await?.remove(0);
+            Ok(HttpResponse::Ok().json(country_json))
+        },
+        None => Err(country_not_found(db, &input_value).await?),
     }
 }
 
 
+#[get("/countries/id/{id}")]
+async fn get_country_by_id(_data: web::Data<AppState>, path: web::Path<i32>, query: web::Query<FieldsQuery>) -> Result<HttpResponse, ApiError> {
+    let country_id: i32 = path.into_inner();
+    println!("Received get input for country id: {}", country_id);
+    let fields = parse_fields(query.fields.as_deref())?;
+
+    let db = &_data.env.db;
+    let base = resolve_base(db, query.base.as_deref()).await?;
+    match db.run(move |db| db.get_country_by_id(country_id)).await? {
+        Some(c) => {
+            let country_json = countries_for_return(db, vec![c], &fields, base.as_ref()).await?.remove(0);
+            Ok(HttpResponse::Ok().json(country_json))
+        },
+        None => Err(ApiError::NotFound("Country not found".to_string(), format!("No country found with id: {}", country_id))),
+    }
+}
+
+/// Upper-cases `key` if it has the shape of an ISO 3166 alpha-2, alpha-3 or numeric code.
+fn iso_code(key: &str) -> Option<String> {
+    let key = key.trim();
+    let alphabetic = (key.len() == 2 || key.len() == 3) && key.chars().all(|c| c.is_ascii_alphabetic());
+    let numeric = key.len() == 3 && key.chars().all(|c| c.is_ascii_digit());
+    if alphabetic || numeric {
+        Some(key.to_ascii_uppercase())
+    } else {
+        None
+    }
+}
+
+/// 404 for a name that resolved to no country, suggesting the stored names closest to it.
+async fn country_not_found(db: &DbPool, name: &str) -> Result<ApiError, ApiError> {
+    let names = db.run(|db| db.get_country_names()).await?;
+    Ok(ApiError::NotFoundWithSuggestions(
+        "Country not found".to_string(),
+        format!("No country found with name: {}", name),
+        suggest_names(name, names),
+    ))
+}
+
+
 
 #[delete("/countries/{name}")]
-async fn delete_country_data(_data: web::Data<AppState>, path: web::Path<String>) -> impl Responder {
+async fn delete_country_data(_data: web::Data<AppState>, path: web::Path<String>) -> Result<HttpResponse, ApiError> {
     let input_value: String = path.into_inner();
     println!("Received Delete input for details: {}", input_value);
     if input_value.trim().is_empty() {
-        let json_response = serde_json::json!({
-            "error": "Validation failed",
-            "details": {
-                "name": "is required"
-            }
-        });
-        return HttpResponse::BadRequest().json(json_response);
+        return Err(ApiError::invalid("name", "is required"));
     }
 
     let db = &_data.env.db;
 
-    match db.delete_country_by_name(&input_value) {
-        Ok(deleted_count) => {
-            if deleted_count > 0 {
-                let json_response = serde_json::json!({
-                    "status": "success",
-                    "message": format!("Country '{}' deleted successfully", input_value)
-                });
-                return HttpResponse::Ok().json(json_response);
-            } else {
-                let json_response = serde_json::json!({
-                    "error": "Country not found",
-                    "details": format!("No country found with name: {}", input_value)
-                });
-                return HttpResponse::NotFound().json(json_response);
-            }
-        },
-        Err(e) => {
-            println!("Error deleting country from database: {}", e);
-            let json_response = serde_json::json!({
-                "error": "Internal Server Error",
-                "details": "Error deleting country from database"
-            });
-            return HttpResponse::InternalServerError().json(json_response);
-        }
+    let delete_name = input_value.clone();
+    let deleted_count = db.run(move |db| db.delete_country_by_name(&delete_name)).await?;
+    if deleted_count == 0 {
+        return Err(country_not_found(db, &input_value).await?);
+    }
+    if let Err(e) = _data.suggest.reload(db).await {
+        println!("Error rebuilding suggest index: {}", e);
     }
+
+    let json_response = serde_json::json!({
+        "status": "success",
+        "message": format!("Country '{}' deleted successfully", input_value)
+    });
+    Ok(HttpResponse::Ok().json(json_response))
 }
 
 #[get("/status")]
-async fn get_countries_and_last_refreash(_data: web::Data<AppState>) -> impl Responder {
+async fn get_countries_and_last_refreash(_data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
     println!("Received get input for details: countries and last refreash");
 
-    let all_countries = get_all_countries(_data);
-    match all_countries {
-        Ok(countries) => {
-            let last_refreshed = countries[0].last_refreshed_at.clone();
-            if last_refreshed.is_none() {
-                let json_response = serde_json::json!({
-                    "error": "No countries found in database",
-                    "details": "Countries data might not have been refreshed yet"
-                });
-                return HttpResponse::NotFound().json(json_response);
-            }
-            let json_response = serde_json::json!({
-                "total_countries": countries.len(),
-                "last_refreshed_at": last_refreshed,
-            });
-            return HttpResponse::Ok().json(json_response);
-        },
-        Err(e) => {
-            println!("Error retrieving countries from database: {}", e);
-            let json_response = serde_json::json!({
-                "error": "Internal Server Error",
-                "details": "Error retrieving countries from database"
-            });
-            return HttpResponse::InternalServerError().json(json_response);
-        }
+    let latest = CountryQuery { limit: 1, ..CountryQuery::default() };
+    let result = _data.env.db.run(move |db| db.search_countries(&latest)).await?;
+    let last_refreshed = result.countries.first().and_then(|c| c.last_refreshed_at).map(|dt| dt.to_string());
+    if last_refreshed.is_none() {
+        return Err(ApiError::NotFound(
+            "No countries found in database".to_string(),
+            "Countries data might not have been refreshed yet".to_string(),
+        ));
     }
+    let json_response = serde_json::json!({
+        "total_countries": result.total,
+        "last_refreshed_at": last_refreshed,
+    });
+    Ok(HttpResponse::Ok().json(json_response))
 }
 
 
 #[get("/countries/image")]
-async fn get_summary_image(req: HttpRequest, _data: web::Data<AppState>) -> impl Responder {
+async fn get_summary_image(req: HttpRequest, _data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
     println!("Received request to generate summary image");
     let path: PathBuf = Path::new("cache/summary.png").to_path_buf();
 
-    if path.exists() {
-        match NamedFile::open(&path) {
-            Ok(file) => {
-                let file = file.set_content_type(mime::IMAGE_PNG);
-                // ✅ Pass the real HttpRequest here
-                file.into_response(&req)
-            }
-            Err(_) => HttpResponse::InternalServerError().finish(),
+    if !path.exists() {
+        return Err(ApiError::NotFound(
+            "Summary image not found".to_string(),
+            "Trigger a refresh to generate the summary image".to_string(),
+        ));
+    }
+
+    match NamedFile::open(&path) {
+        Ok(file) => {
+            let file = file.set_content_type(mime::IMAGE_PNG);
+            // ✅ Pass the real HttpRequest here
+            Ok(file.into_response(&req))
         }
-    } else {
-        HttpResponse::NotFound().json(serde_json::json!({
-            "error": "Summary image not found"
-        }))
+        Err(e) => Err(ApiError::internal("Error reading summary image", e)),
     }
 }
 
 
+/// The five countries with the highest estimated GDP, as shown on the summary image.
+fn top_by_estimated_gdp(countries: &[Country]) -> Vec<Country> {
+    let mut sorted = countries.to_vec();
+    sorted.sort_by(|a, b| b.estimated_gdp.partial_cmp(&a.estimated_gdp).unwrap_or(std::cmp::Ordering::Equal));
+    sorted.truncate(5);
+    sorted
+}
+
 fn generate_summary_image(countries: Vec<Country>, timestamp: String) -> std::io::Result<()> {
     
     // ✅ Prepare directory
@@ -456,11 +898,7 @@ fn generate_summary_image(countries: Vec<Country>, timestamp: String) -> std::io
     // ✅ Calculate stats
     let total_countries = countries.len();
 
-    // sort by estimated GDP descending
-    let mut sorted = countries.to_vec();
-    sorted.sort_by(|a, b| b.estimated_gdp.partial_cmp(&a.estimated_gdp).unwrap_or(std::cmp::Ordering::Equal));
-
-    let top_5 = sorted.iter().take(5).collect::<Vec<_>>();
+    let top_5 = top_by_estimated_gdp(&countries);
 
     // ✅ Create blank image (800x600 white)
     let mut img = RgbImage::from_pixel(800, 600, Rgb([255, 255, 255]));
@@ -472,7 +910,7 @@ fn generate_summary_image(countries: Vec<Country>, timestamp: String) -> std::io
 
     // ✅ Draw summary
     draw_text_mut(&mut img, Rgb([0, 0, 0]), 30, 30, scale, &font, 
-        &format!("🌍 Country Summary Report"));
+        "🌍 Country Summary Report");
     draw_text_mut(&mut img, Rgb([0, 0, 0]), 30, 80, scale, &font,
         &format!("Total Countries: {}", total_countries));
     draw_text_mut(&mut img, Rgb([0, 0, 0]), 30, 120, scale, &font,
@@ -500,9 +938,266 @@ fn generate_summary_image(countries: Vec<Country>, timestamp: String) -> std::io
         Ok(_) =>  println!("✅ Summary image generated at cache/summary.png"),
         Err(e) => {
             println!("Error saving summary image: {}", e);
-            return Err(Error::new(std::io::ErrorKind::Other, format!("Error saving summary image: {}", e)));
+            return Err(Error::other(format!("Error saving summary image: {}", e)));
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use serde_json::json;
+    use crate::routes::test_support::{country, get_json, rate, seeded};
+
+    fn names(body: &serde_json::Value) -> Vec<&str> {
+        body["data"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect()
+    }
+
+    #[actix_web::test]
+    async fn filter_on_currency_code_agrees_with_currency_parameter() {
+        let db = seeded(&[
+            country(json!({ "name": "Nigeria", "currencies": [{ "code": "NGN" }] })),
+            country(json!({ "name": "Zimbabwe", "currencies": [{ "code": "ZWL" }, { "code": "USD" }] })),
+            country(json!({ "name": "Antarctica" })),
+        ], &[rate("NGN", 1460.5), rate("USD", 1.0)]);
+
+        let (status, by_filter) = get_json(&db, "/api/v1/countries?filter=currency_code%20%3D%20USD").await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, by_parameter) = get_json(&db, "/api/v1/countries?currency=USD").await;
+        assert_eq!(names(&by_filter), ["Zimbabwe"]);
+        assert_eq!(names(&by_filter), names(&by_parameter));
+        let (_, lowercase) = get_json(&db, "/api/v1/countries?currency=usd").await;
+        assert_eq!(names(&lowercase), ["Zimbabwe"]);
+
+        let (_, others) = get_json(&db, "/api/v1/countries?filter=currency_code%20!%3D%20USD").await;
+        assert_eq!(names(&others), ["Nigeria"]);
+    }
+
+    #[actix_web::test]
+    async fn malformed_filter_is_a_validation_error() {
+        let db = seeded(&[], &[]);
+        let (status, body) = get_json(&db, "/api/v1/countries?filter=population%20%3E%20many").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["filter"], "expected a number but found 'many' at position 14");
+    }
+
+    fn countries_with_populations() -> Vec<crate::models::countries_models::Country> {
+        [("Chad", Some(17)), ("Mali", Some(22)), ("Niger", None), ("Togo", Some(9)), ("Benin", Some(13)), ("Gabon", None), ("Ghana", Some(34))]
+            .into_iter()
+            .map(|(name, population)| country(json!({ "name": name, "region": "Africa", "population": population })))
+            .collect()
+    }
+
+    // Follows `rel` links from `uri` until there are none, collecting every page's names.
+    async fn walk(db: &crate::config::db::DbPool, uri: &str, rel: &str) -> Vec<Vec<String>> {
+        let mut pages = Vec::new();
+        let mut next = Some(uri.to_string());
+        while let Some(uri) = next {
+            let (status, body) = get_json(db, &uri).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            pages.push(names(&body).into_iter().map(str::to_string).collect());
+            next = body[rel].as_str().map(str::to_string);
+        }
+        pages
+    }
+
+    #[actix_web::test]
+    async fn cursor_pages_cover_every_row_once_in_both_directions() {
+        let db = seeded(&countries_with_populations(), &[]);
+
+        let forward = walk(&db, "/api/v1/countries?sort=-population&limit=3&cursor=", "next").await;
+        assert_eq!(forward, [
+            vec!["Ghana", "Mali", "Chad"],
+            vec!["Benin", "Togo", "Niger"],
+            vec!["Gabon"],
+        ]);
+
+        let (_, last) = get_json(&db, "/api/v1/countries?sort=-population&limit=3&cursor=").await;
+        let (_, second) = get_json(&db, last["next"].as_str().unwrap()).await;
+        let (_, third) = get_json(&db, second["next"].as_str().unwrap()).await;
+        let backward = walk(&db, third["prev"].as_str().unwrap(), "prev").await;
+        assert_eq!(backward, [
+            vec!["Benin", "Togo", "Niger"],
+            vec!["Ghana", "Mali", "Chad"],
+        ]);
+        assert!(third["next"].is_null());
+    }
+
+    #[actix_web::test]
+    async fn cursor_does_not_shift_when_rows_are_added_before_it() {
+        let db = seeded(&countries_with_populations(), &[]);
+        let (_, first) = get_json(&db, "/api/v1/countries?sort=-population&limit=3&cursor=").await;
+
+        db.upsert_refresh(&[country(json!({ "name": "Egypt", "region": "Africa", "population": 110 }))], &[]).unwrap();
+        let (_, second) = get_json(&db, first["next"].as_str().unwrap()).await;
+        assert_eq!(names(&second), ["Benin", "Togo", "Niger"]);
+        assert_eq!(second["total"], 8);
+    }
+
+    #[actix_web::test]
+    async fn cursor_keeps_sort_values_when_fields_leave_them_out() {
+        let db = seeded(&countries_with_populations(), &[]);
+        let pages = walk(&db, "/api/v1/countries?sort=-population&fields=name&limit=4&cursor=", "next").await;
+        assert_eq!(pages, [vec!["Ghana", "Mali", "Chad", "Benin"], vec!["Togo", "Niger", "Gabon"]]);
+
+        let (_, body) = get_json(&db, "/api/v1/countries?sort=-population&fields=name&limit=1&cursor=").await;
+        assert_eq!(body["data"][0], json!({ "name": "Ghana" }));
+    }
+
+    #[actix_web::test]
+    async fn offset_pages_report_their_neighbours() {
+        let db = seeded(&countries_with_populations(), &[]);
+        let (_, body) = get_json(&db, "/api/v1/countries?sort=name&limit=3&offset=3").await;
+        assert_eq!(names(&body), ["Ghana", "Mali", "Niger"]);
+        assert_eq!(body["offset"], 3);
+        assert_eq!(body["next"], "/api/v1/countries?sort=name&limit=3&offset=6");
+        assert_eq!(body["prev"], "/api/v1/countries?sort=name&limit=3&offset=0");
+    }
+
+    #[actix_web::test]
+    async fn cursor_is_bound_to_its_query() {
+        let db = seeded(&countries_with_populations(), &[]);
+        let (_, first) = get_json(&db, "/api/v1/countries?sort=name&limit=2&cursor=").await;
+        let next = first["next"].as_str().unwrap().replace("sort=name", "sort=-name");
+
+        let (status, body) = get_json(&db, &next).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["cursor"], "was issued for different query parameters");
+        let (status, _) = get_json(&db, "/api/v1/countries?cursor=not-a-cursor").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn panicking_pipeline_is_reported_as_a_failed_refresh() {
+        let result = super::catch_panic(async { panic!("unexpected upstream shape") }).await;
+        assert!(matches!(result, Err(super::RefreshError::Panicked(message)) if message == "unexpected upstream shape"));
+
+        let result = super::catch_panic(async { Ok(3) }).await;
+        assert!(matches!(result, Ok(3)));
+    }
+
+    #[actix_web::test]
+    async fn multi_key_sort_keeps_null_values_last() {
+        let db = seeded(&[
+            country(json!({ "name": "Chad", "region": "Africa", "population": 17 })),
+            country(json!({ "name": "Peru", "region": "Americas", "population": 33 })),
+            country(json!({ "name": "Niger", "region": "Africa" })),
+            country(json!({ "name": "Ghana", "region": "Africa", "population": 34 })),
+            country(json!({ "name": "Atlantis", "population": 1 })),
+        ], &[]);
+
+        let (status, body) = get_json(&db, "/api/v1/countries?sort=region,-population").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(names(&body), ["Ghana", "Chad", "Niger", "Peru", "Atlantis"]);
+
+        let (_, body) = get_json(&db, "/api/v1/countries?sort=-region,population_asc").await;
+        assert_eq!(names(&body), ["Peru", "Chad", "Ghana", "Niger", "Atlantis"]);
+    }
+
+    #[actix_web::test]
+    async fn unknown_or_repeated_sort_fields_are_validation_errors() {
+        let db = seeded(&[country(json!({ "name": "Chad" }))], &[]);
+        let (status, body) = get_json(&db, "/api/v1/countries?sort=-area").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["sort"], "unknown field '-area', expected one of name, population, gdp, exchange_rate, capital, region");
+
+        let (_, body) = get_json(&db, "/api/v1/countries?sort=gdp,gdp_desc").await;
+        assert_eq!(body["details"]["sort"], "field 'gdp' is listed more than once");
+    }
+
+    #[actix_web::test]
+    async fn name_lookups_fold_case_accents_and_alternate_spellings() {
+        let db = seeded(&[
+            country(json!({ "name": "Côte d'Ivoire", "alpha2Code": "ci", "altSpellings": ["CI", "Ivory Coast"] })),
+            country(json!({ "name": "Nigeria", "alpha3Code": "NGA" })),
+        ], &[]);
+
+        for uri in ["/api/v1/countries/cote%20divoire", "/api/v1/countries/C%C3%94TE-D'IVOIRE", "/api/v1/countries/ivory%20coast", "/api/v1/countries/ci"] {
+            let (status, body) = get_json(&db, uri).await;
+            assert_eq!(status, StatusCode::OK, "{}", uri);
+            assert_eq!(body["name"], "Côte d'Ivoire", "{}", uri);
+        }
+        let (_, body) = get_json(&db, "/api/v1/countries/NIGERIA").await;
+        assert_eq!(body["name"], "Nigeria");
+    }
+
+    #[actix_web::test]
+    async fn unknown_name_suggests_the_closest_stored_names() {
+        let db = seeded(&[
+            country(json!({ "name": "Nigeria" })),
+            country(json!({ "name": "Niger" })),
+            country(json!({ "name": "Norway" })),
+        ], &[]);
+
+        let (status, body) = get_json(&db, "/api/v1/countries/Nigerai").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["details"], "No country found with name: Nigerai");
+        assert_eq!(body["suggestions"], json!(["Nigeria", "Niger"]));
+
+        let (_, body) = get_json(&db, "/api/v1/countries/Atlantis").await;
+        assert_eq!(body["suggestions"], json!([]));
+    }
+
+    #[actix_web::test]
+    async fn suggest_answers_from_the_index_and_validates_its_parameters() {
+        let db = seeded(&[
+            country(json!({ "name": "Nigeria", "capital": "Abuja", "alpha3Code": "NGA" })),
+            country(json!({ "name": "Niger", "capital": "Niamey" })),
+        ], &[]);
+
+        let (status, body) = get_json(&db, "/api/v1/countries/suggest?q=nig&limit=1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["query"], "nig");
+        assert_eq!(body["data"], json!([{
+            "id": 2, "name": "Niger", "capital": "Niamey",
+            "alpha2_code": null, "alpha3_code": null, "numeric_code": null, "matched": "name_prefix",
+        }]));
+
+        let (status, body) = get_json(&db, "/api/v1/countries/suggest?q=%20").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["q"], "is required");
+        let (_, body) = get_json(&db, "/api/v1/countries/suggest?q=ni&limit=51").await;
+        assert_eq!(body["details"]["limit"], "must be between 1 and 50");
+    }
+
+    #[test]
+    fn rebase_factor_needs_a_shared_base_and_a_positive_rate() {
+        let euro = rate("EUR", 0.5);
+        assert_eq!(super::rebase_factor(Some("USD"), &euro), Some(0.5));
+        assert_eq!(super::rebase_factor(Some("GBP"), &euro), None);
+        assert_eq!(super::rebase_factor(None, &euro), None);
+        assert_eq!(super::rebase_factor(Some("USD"), &rate("EUR", 0.0)), None);
+    }
+
+    #[actix_web::test]
+    async fn base_reexpresses_rates_and_gdp() {
+        let mut nigeria = country(json!({ "name": "Nigeria", "currencies": [{ "code": "NGN" }, { "code": "EUR" }] }));
+        nigeria.set_exchange_rate(1500.0);
+        nigeria.set_estimated_gdp(1000.0);
+        let db = seeded(&[nigeria], &[rate("NGN", 1500.0), rate("EUR", 0.5)]);
+
+        let (status, body) = get_json(&db, "/api/v1/countries/Nigeria?base=eur").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["base_currency"], "EUR");
+        assert_eq!(body["exchange_rate"], 3000.0);
+        assert_eq!(body["estimated_gdp"], 500.0);
+        assert_eq!(body["currencies"][1]["exchange_rate"], 1.0);
+
+        let (_, body) = get_json(&db, "/api/v1/countries?fields=name,estimated_gdp").await;
+        assert_eq!(body["data"][0], json!({ "name": "Nigeria", "estimated_gdp": 1000.0, "base_currency": "USD" }));
+    }
+
+    #[actix_web::test]
+    async fn base_must_be_a_stored_currency_with_a_rate() {
+        let db = seeded(&[country(json!({ "name": "Ghana", "currencies": [{ "code": "GHS" }] }))], &[]);
+        let (status, body) = get_json(&db, "/api/v1/countries?base=XYZ").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"]["base"], "unknown currency code 'XYZ'");
+
+        let (_, body) = get_json(&db, "/api/v1/countries/Ghana?base=ghs").await;
+        assert_eq!(body["details"]["base"], "no exchange rate is stored for GHS");
+    }
+}
@@ -0,0 +1,466 @@
+use crate::{AppState, models::models::{Country as CountryModel, Currency as CurrencyModel, ExchangeRate}, routes::api_error::ApiError};
+use actix_web::{HttpResponse, get, web};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+
+#[get("/currencies")]
+async fn get_currencies(_data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let db = &_data.env.db;
+    let currencies = db.run(|db| db.get_currencies()).await?;
+    let codes: Vec<String> = currencies.iter().map(|c| c.code.clone()).collect();
+    let users = db.run(move |db| db.get_currency_countries(&codes)).await?;
+
+    // Currencies no stored country uses only come from the rate table; list them apart.
+    let (matched, unmatched): (Vec<CurrencyModel>, Vec<CurrencyModel>) = currencies
+        .into_iter()
+        .partition(|currency| users.iter().any(|(code, _)| code == &currency.code));
+
+    let data: Vec<serde_json::Value> = matched.iter().map(|currency| structure_currency_for_return(currency, &users)).collect();
+    let unmatched_rates: Vec<serde_json::Value> = unmatched
+        .iter()
+        .map(|currency| serde_json::json!({
+            "code": currency.code,
+            "exchange_rate": currency.exchange_rate,
+            "rate_updated_at": currency.rate_updated_at,
+            "rate_base": currency.rate_base,
+        }))
+        .collect();
+
+    let json_response = serde_json::json!({
+        "total": data.len(),
+        "data": data,
+        "unmatched_rates": unmatched_rates,
+    });
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+
+#[get("/currencies/{code}")]
+async fn get_currency_by_code(_data: web::Data<AppState>, path: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let code = path.into_inner().trim().to_ascii_uppercase();
+    println!("Received get input for currency: {}", code);
+    if code.is_empty() {
+        return Err(ApiError::invalid("code", "is required"));
+    }
+
+    let db = &_data.env.db;
+    let lookup_code = code.clone();
+    let currency = match db.run(move |db| db.get_currency(&lookup_code)).await? {
+        Some(currency) => currency,
+        None => return Err(currency_not_found(&code)),
+    };
+    let codes = vec![currency.code.clone()];
+    let users = db.run(move |db| db.get_currency_countries(&codes)).await?;
+
+    Ok(HttpResponse::Ok().json(structure_currency_for_return(&currency, &users)))
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct HistoryQuery {
+    from: Option<String>,
+    to: Option<String>,
+    interval: Option<String>,
+}
+
+/// Width of one point of a rate series; `Raw` keeps every stored fetch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Interval {
+    Raw,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+const INTERVALS: [(&str, Interval); 5] = [
+    ("raw", Interval::Raw),
+    ("hour", Interval::Hour),
+    ("day", Interval::Day),
+    ("week", Interval::Week),
+    ("month", Interval::Month),
+];
+
+impl Interval {
+    /// Start of the bucket `at` falls in. Weeks start on Monday.
+    fn bucket(&self, at: NaiveDateTime) -> NaiveDateTime {
+        let day = at.date().and_hms_opt(0, 0, 0).unwrap();
+        match self {
+            Interval::Raw => at,
+            Interval::Hour => day + Duration::hours(at.hour() as i64),
+            Interval::Day => day,
+            Interval::Week => day - Duration::days(at.weekday().num_days_from_monday() as i64),
+            Interval::Month => at.date().with_day(1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        }
+    }
+}
+
+/// The stored rates of one currency over time, one point per `interval` holding the last rate fetched in it,
+/// with the lowest, highest and overall percent change of the series.
+#[get("/currencies/{code}/history")]
+async fn get_currency_history(_data: web::Data<AppState>, path: web::Path<String>, query: web::Query<HistoryQuery>) -> Result<HttpResponse, ApiError> {
+    let code = path.into_inner().trim().to_ascii_uppercase();
+    println!("Received history input for currency: {} {:?}", code, query);
+
+    let mut errors = serde_json::Map::new();
+    let from = parse_instant("from", query.from.as_deref(), false, &mut errors);
+    let to = parse_instant("to", query.to.as_deref(), true, &mut errors);
+    let interval_name = query.interval.as_deref().map(|i| i.trim().to_ascii_lowercase()).unwrap_or_else(|| "raw".to_string());
+    let interval = INTERVALS.iter().find(|(name, _)| *name == interval_name).map(|(_, interval)| *interval);
+    if interval.is_none() {
+        let expected: Vec<&str> = INTERVALS.iter().map(|(name, _)| *name).collect();
+        errors.insert("interval".to_string(), serde_json::json!(format!("must be one of {}", expected.join(", "))));
+    }
+    if let (Some(from), Some(to)) = (from, to) && from > to {
+        errors.insert("from".to_string(), serde_json::json!("must not be after to"));
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(serde_json::Value::Object(errors)));
+    }
+    let interval = interval.unwrap_or(Interval::Raw);
+
+    let db = &_data.env.db;
+    let lookup_code = code.clone();
+    let (currency, history) = db.run(move |db| {
+        Ok((db.get_currency(&lookup_code)?, db.get_exchange_rate_history(&lookup_code, from, to)?))
+    }).await?;
+    if currency.is_none() {
+        return Err(currency_not_found(&code));
+    }
+
+    // Rates quoted against different bases do not compare, so the series follows the latest base.
+    let base = history.last().and_then(|r| r.rate_base.clone());
+    let series: Vec<&ExchangeRate> = history.iter().filter(|r| r.rate_base == base).collect();
+    let points = bucket_rates(&series, interval);
+
+    let json_response = serde_json::json!({
+        "code": code,
+        "base": base,
+        "interval": interval_name,
+        "from": from,
+        "to": to,
+        "summary": summarize_rates(&points),
+        "data": points.iter().map(|(at, rate, samples)| serde_json::json!({
+            "at": at,
+            "rate": rate,
+            "samples": samples,
+        })).collect::<Vec<_>>(),
+    });
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Parses `from`/`to` as an RFC 3339 timestamp, a `YYYY-MM-DDTHH:MM:SS` UTC time or a bare date.
+/// A bare date covers its whole day, so `to=2025-11-05` includes rates fetched that evening.
+fn parse_instant(name: &str, value: Option<&str>, end_of_day: bool, errors: &mut serde_json::Map<String, serde_json::Value>) -> Option<NaiveDateTime> {
+    let value = value.map(str::trim).filter(|v| !v.is_empty())?;
+    let parsed = chrono::DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+            match end_of_day {
+                true => date.and_hms_nano_opt(23, 59, 59, 999_999_999),
+                false => date.and_hms_opt(0, 0, 0),
+            }
+        });
+    if parsed.is_none() {
+        errors.insert(name.to_string(), serde_json::json!("must be a date (YYYY-MM-DD) or an RFC 3339 timestamp"));
+    }
+    parsed
+}
+
+/// `(bucket start, last rate in the bucket, rates in the bucket)` for `series`, which is oldest first.
+fn bucket_rates(series: &[&ExchangeRate], interval: Interval) -> Vec<(NaiveDateTime, f64, usize)> {
+    let mut points: Vec<(NaiveDateTime, f64, usize)> = Vec::new();
+    for rate in series {
+        let at = interval.bucket(rate.fetched_at);
+        match points.last_mut() {
+            Some(point) if point.0 == at => {
+                point.1 = rate.rate;
+                point.2 += 1;
+            },
+            _ => points.push((at, rate.rate, 1)),
+        }
+    }
+    points
+}
+
+fn summarize_rates(points: &[(NaiveDateTime, f64, usize)]) -> serde_json::Value {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return serde_json::json!({ "points": 0, "min": null, "max": null, "first": null, "last": null, "change": null, "change_percent": null });
+    };
+    let min = points.iter().fold(first, |min, p| if p.1 < min.1 { p } else { min });
+    let max = points.iter().fold(first, |max, p| if p.1 > max.1 { p } else { max });
+    let change = last.1 - first.1;
+    let change_percent = (first.1 != 0.0).then(|| change / first.1 * 100.0);
+
+    serde_json::json!({
+        "points": points.len(),
+        "min": { "rate": min.1, "at": min.0 },
+        "max": { "rate": max.1, "at": max.0 },
+        "first": { "rate": first.1, "at": first.0 },
+        "last": { "rate": last.1, "at": last.0 },
+        "change": change,
+        "change_percent": change_percent,
+    })
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct ConvertQuery {
+    from: Option<String>,
+    to: Option<String>,
+    amount: Option<String>,
+}
+
+/// Converts `amount` of `from` into `to` through the stored rate table, whose rates are all quoted
+/// against one base currency.
+#[get("/convert")]
+async fn convert_currency(_data: web::Data<AppState>, query: web::Query<ConvertQuery>) -> Result<HttpResponse, ApiError> {
+    let mut errors = serde_json::Map::new();
+    let from = query.from.as_deref().map(|c| c.trim().to_ascii_uppercase()).filter(|c| !c.is_empty());
+    let to = query.to.as_deref().map(|c| c.trim().to_ascii_uppercase()).filter(|c| !c.is_empty());
+    if from.is_none() {
+        errors.insert("from".to_string(), serde_json::json!("is required"));
+    }
+    if to.is_none() {
+        errors.insert("to".to_string(), serde_json::json!("is required"));
+    }
+    let amount = match query.amount.as_deref() {
+        None => 1.0,
+        Some(raw) => match raw.trim().parse::<f64>() {
+            Ok(amount) if amount.is_finite() && amount >= 0.0 => amount,
+            Ok(amount) if amount.is_finite() && amount < 0.0 => {
+                errors.insert("amount".to_string(), serde_json::json!("must not be negative"));
+                0.0
+            },
+            _ => {
+                errors.insert("amount".to_string(), serde_json::json!("must be a number"));
+                0.0
+            }
+        },
+    };
+    let (Some(from), Some(to)) = (from, to) else {
+        return Err(ApiError::Validation(serde_json::Value::Object(errors)));
+    };
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(serde_json::Value::Object(errors)));
+    }
+
+    let db = &_data.env.db;
+    let codes = (from.clone(), to.clone());
+    let (from_currency, to_currency) = db.run(move |db| Ok((db.get_currency(&codes.0)?, db.get_currency(&codes.1)?))).await?;
+    let from_currency = from_currency.ok_or_else(|| currency_not_found(&from))?;
+    let to_currency = to_currency.ok_or_else(|| currency_not_found(&to))?;
+    let rate = cross_rate(&from_currency, &to_currency)?;
+
+    // A cross rate is only as recent as the older of its two legs.
+    let rate_updated_at = match (from_currency.rate_updated_at, to_currency.rate_updated_at) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+
+    let json_response = serde_json::json!({
+        "from": from,
+        "to": to,
+        "amount": amount,
+        "rate": rate,
+        "result": amount * rate,
+        "base": from_currency.rate_base,
+        "rate_updated_at": rate_updated_at,
+    });
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+/// Units of `to` per one unit of `from`, derived from both currencies' rates against the stored base.
+pub fn cross_rate(from: &CurrencyModel, to: &CurrencyModel) -> Result<f64, ApiError> {
+    let no_rate = |code: &str| ApiError::NotFound(
+        "Exchange rate not found".to_string(),
+        format!("No exchange rate stored for currency code: {}", code),
+    );
+    let from_rate = from.exchange_rate.filter(|r| *r > 0.0).ok_or_else(|| no_rate(&from.code))?;
+    let to_rate = to.exchange_rate.filter(|r| *r > 0.0).ok_or_else(|| no_rate(&to.code))?;
+    if from.rate_base != to.rate_base {
+        return Err(ApiError::NotFound(
+            "Exchange rate not found".to_string(),
+            format!("Rates for {} and {} are quoted against different base currencies", from.code, to.code),
+        ));
+    }
+    Ok(to_rate / from_rate)
+}
+
+fn currency_not_found(code: &str) -> ApiError {
+    ApiError::NotFound("Currency not found".to_string(), format!("No currency found with code: {}", code))
+}
+
+/// Response shape of a currency with the countries that use it; `users` may cover other currencies too.
+fn structure_currency_for_return(currency: &CurrencyModel, users: &[(String, CountryModel)]) -> serde_json::Value {
+    let countries: Vec<&CountryModel> = users
+        .iter()
+        .filter(|(code, _)| code == &currency.code)
+        .map(|(_, country)| country)
+        .collect();
+    let total_population: i64 = countries.iter().filter_map(|c| c.population).sum();
+
+    serde_json::json!({
+        "code": currency.code,
+        "name": currency.name,
+        "symbol": currency.symbol,
+        "exchange_rate": currency.exchange_rate,
+        "rate_updated_at": currency.rate_updated_at,
+        "rate_base": currency.rate_base,
+        "country_count": countries.len(),
+        "total_population": total_population,
+        "countries": countries.iter().map(|c| serde_json::json!({
+            "id": c.id,
+            "name": c.name,
+            "alpha2_code": c.alpha2_code,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use chrono::NaiveDateTime;
+    use serde_json::json;
+    use super::Interval;
+    use crate::models::models::Currency as CurrencyModel;
+    use crate::routes::test_support::{get_json, rate, seeded};
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn fetched(code: &str, value: f64, fetched_at: &str, base: &str) -> CurrencyModel {
+        CurrencyModel { rate_updated_at: Some(at(fetched_at)), rate_base: Some(base.to_string()), ..rate(code, value) }
+    }
+
+    #[actix_web::test]
+    async fn converts_through_the_shared_base() {
+        let db = seeded(&[], &[rate("NGN", 1500.0), rate("EUR", 0.9)]);
+        let (status, body) = get_json(&db, "/api/v1/convert?from=ngn&to=EUR&amount=3000").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["from"].as_str(), body["to"].as_str()), (Some("NGN"), Some("EUR")));
+        assert!((body["rate"].as_f64().unwrap() - 0.0006).abs() < 1e-12);
+        assert!((body["result"].as_f64().unwrap() - 1.8).abs() < 1e-9);
+        assert_eq!(body["base"], "USD");
+
+        let (_, body) = get_json(&db, "/api/v1/convert?from=EUR&to=NGN").await;
+        assert_eq!(body["amount"], 1.0);
+    }
+
+    #[actix_web::test]
+    async fn missing_codes_and_bad_amounts_are_validation_errors() {
+        let db = seeded(&[], &[rate("NGN", 1500.0), rate("EUR", 0.9)]);
+        let (status, body) = get_json(&db, "/api/v1/convert?to=EUR").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"], json!({ "from": "is required" }));
+
+        let (_, body) = get_json(&db, "/api/v1/convert?from=NGN&to=%20&amount=ten").await;
+        assert_eq!(body["details"], json!({ "to": "is required", "amount": "must be a number" }));
+
+        let (status, body) = get_json(&db, "/api/v1/convert?from=NGN&to=EUR&amount=-5").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"], json!({ "amount": "must not be negative" }));
+    }
+
+    #[actix_web::test]
+    async fn unknown_code_is_not_found() {
+        let db = seeded(&[], &[rate("NGN", 1500.0)]);
+        let (status, body) = get_json(&db, "/api/v1/convert?from=NGN&to=XYZ").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Currency not found");
+        assert_eq!(body["details"], "No currency found with code: XYZ");
+    }
+
+    #[actix_web::test]
+    async fn rates_against_different_bases_do_not_convert() {
+        let mut euro = rate("EUR", 1.0);
+        euro.rate_base = Some("EUR".to_string());
+        let db = seeded(&[], &[rate("NGN", 1500.0), euro]);
+
+        let (status, body) = get_json(&db, "/api/v1/convert?from=NGN&to=EUR").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Exchange rate not found");
+        assert_eq!(body["details"], "Rates for NGN and EUR are quoted against different base currencies");
+    }
+
+    #[test]
+    fn buckets_start_on_the_hour_day_monday_and_first_of_month() {
+        let sunday_evening = at("2025-11-09 18:45");
+        assert_eq!(Interval::Raw.bucket(sunday_evening), sunday_evening);
+        assert_eq!(Interval::Hour.bucket(sunday_evening), at("2025-11-09 18:00"));
+        assert_eq!(Interval::Day.bucket(sunday_evening), at("2025-11-09 00:00"));
+        assert_eq!(Interval::Week.bucket(sunday_evening), at("2025-11-03 00:00"));
+        assert_eq!(Interval::Week.bucket(at("2025-11-10 00:00")), at("2025-11-10 00:00"));
+        assert_eq!(Interval::Month.bucket(sunday_evening), at("2025-11-01 00:00"));
+    }
+
+    fn naira_history() -> crate::config::db::DbPool {
+        let db = seeded(&[], &[]);
+        for (value, fetched_at) in [(1500.0, "2025-11-03 08:00"), (1480.0, "2025-11-03 20:00"), (1520.0, "2025-11-05 09:00"), (1450.0, "2025-11-10 07:30")] {
+            db.upsert_refresh(&[], &[fetched("NGN", value, fetched_at, "USD")]).unwrap();
+        }
+        db
+    }
+
+    #[actix_web::test]
+    async fn history_keeps_the_last_rate_of_each_bucket() {
+        let db = naira_history();
+        let (status, body) = get_json(&db, "/api/v1/currencies/ngn/history?interval=day").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&body["code"], &body["base"]), (&json!("NGN"), &json!("USD")));
+        assert_eq!(body["data"], json!([
+            { "at": "2025-11-03T00:00:00", "rate": 1480.0, "samples": 2 },
+            { "at": "2025-11-05T00:00:00", "rate": 1520.0, "samples": 1 },
+            { "at": "2025-11-10T00:00:00", "rate": 1450.0, "samples": 1 },
+        ]));
+        let summary = &body["summary"];
+        assert_eq!(summary["points"], 3);
+        assert_eq!(summary["min"], json!({ "rate": 1450.0, "at": "2025-11-10T00:00:00" }));
+        assert_eq!(summary["max"], json!({ "rate": 1520.0, "at": "2025-11-05T00:00:00" }));
+        assert_eq!(summary["change"], -30.0);
+        assert!((summary["change_percent"].as_f64().unwrap() + 30.0 / 14.8).abs() < 1e-9);
+
+        let (_, body) = get_json(&db, "/api/v1/currencies/NGN/history?interval=week").await;
+        let samples: Vec<_> = body["data"].as_array().unwrap().iter().map(|p| (p["rate"].as_f64().unwrap(), p["samples"].as_u64().unwrap())).collect();
+        assert_eq!(samples, [(1520.0, 3), (1450.0, 1)]);
+    }
+
+    #[actix_web::test]
+    async fn history_window_covers_whole_days() {
+        let db = naira_history();
+        let (_, body) = get_json(&db, "/api/v1/currencies/NGN/history?from=2025-11-04&to=2025-11-05").await;
+        assert_eq!(body["interval"], "raw");
+        assert_eq!(body["data"], json!([{ "at": "2025-11-05T09:00:00", "rate": 1520.0, "samples": 1 }]));
+
+        let (_, body) = get_json(&db, "/api/v1/currencies/NGN/history?from=2026-01-01").await;
+        assert_eq!(body["data"], json!([]));
+        assert_eq!(body["summary"]["change_percent"], json!(null));
+    }
+
+    #[actix_web::test]
+    async fn history_follows_the_latest_base() {
+        let db = seeded(&[], &[]);
+        db.upsert_refresh(&[], &[fetched("GHS", 12.0, "2025-11-03 08:00", "EUR")]).unwrap();
+        db.upsert_refresh(&[], &[fetched("GHS", 11.0, "2025-11-04 08:00", "USD")]).unwrap();
+
+        let (_, body) = get_json(&db, "/api/v1/currencies/GHS/history").await;
+        assert_eq!(body["base"], "USD");
+        assert_eq!(body["summary"]["points"], 1);
+    }
+
+    #[actix_web::test]
+    async fn history_rejects_bad_parameters_and_unknown_codes() {
+        let db = naira_history();
+        let (status, body) = get_json(&db, "/api/v1/currencies/NGN/history?interval=year&from=2025-11-05&to=2025-11-04").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"], json!({ "interval": "must be one of raw, hour, day, week, month", "from": "must not be after to" }));
+
+        let (_, body) = get_json(&db, "/api/v1/currencies/NGN/history?to=yesterday").await;
+        assert_eq!(body["details"]["to"], "must be a date (YYYY-MM-DD) or an RFC 3339 timestamp");
+
+        let (status, _) = get_json(&db, "/api/v1/currencies/XYZ/history").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{AppState, jobs::refresh_jobs::{JobPhase, RefreshJob}, routes::api_error::ApiError};
+use actix_web::{HttpResponse, get, web};
+
+
+#[get("/jobs/{id}")]
+async fn get_job_status(_data: web::Data<AppState>, path: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let job_id: String = path.into_inner();
+    println!("Received get input for job status: {}", job_id);
+
+    if let Some(job) = _data.jobs.get(&job_id) {
+        return Ok(HttpResponse::Ok().json(job));
+    }
+
+    // Jobs evicted from memory (or run before a restart) are still on record in `refresh_runs`.
+    let lookup_id = job_id.clone();
+    match _data.env.db.run(move |db| db.get_refresh_run_by_job_id(&lookup_id)).await? {
+        Some(run) => {
+            let succeeded = run.status == "success";
+            let phase = match run.status.as_str() {
+                "success" => JobPhase::Completed,
+                "running" => JobPhase::Running,
+                _ => JobPhase::Failed,
+            };
+            let job = RefreshJob {
+                id: run.job_id,
+                triggered_by: run.triggered_by,
+                phase,
+                countries_processed: run.countries_processed as usize,
+                errors: run.error.map(|e| vec![e]).unwrap_or_default(),
+                result: if succeeded {
+                    Some(serde_json::json!({ "countries_processed": run.countries_processed }))
+                } else {
+                    None
+                },
+                started_at: run.started_at,
+                finished_at: run.finished_at,
+            };
+            Ok(HttpResponse::Ok().json(job))
+        },
+        None => Err(ApiError::NotFound(
+            "Job not found".to_string(),
+            format!("No refresh job found with id: {}", job_id),
+        )),
+    }
+}
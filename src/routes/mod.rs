@@ -1,3 +1,9 @@
+pub mod api_error;
 pub mod healthz;
 pub mod countries_data;
-// pub mod me;
\ No newline at end of file
+pub mod currencies;
+pub mod jobs;
+pub mod pagination;
+// pub mod me;
+#[cfg(test)]
+pub mod test_support;
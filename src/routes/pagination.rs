@@ -0,0 +1,183 @@
+use actix_web::{HttpRequest, HttpResponse, http::header};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use crate::models::models::Country as CountryModel;
+use crate::repository::{Keyset, SortKey};
+use crate::routes::api_error::ApiError;
+
+pub const DEFAULT_LIMIT: usize = 50;
+pub const MAX_LIMIT: usize = 250;
+
+/// Query parameters that control paging rather than filtering.
+const PAGING_PARAMS: [&str; 3] = ["limit", "offset", "cursor"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PageMode {
+    /// `?limit=&offset=`, links carry explicit offsets.
+    Offset,
+    /// `?cursor=`, links carry opaque cursors bound to the current filters. A cursor holds the
+    /// sort values of the row next to the page, so rows inserted or deleted meanwhile never shift it.
+    Cursor,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CursorToken {
+    k: Keyset,
+    q: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub limit: usize,
+    pub offset: usize,
+    /// Where a cursor page starts; `None` for the first page and in offset mode.
+    pub keyset: Option<Keyset>,
+    pub mode: PageMode,
+    fingerprint: String,
+    // Neighbouring cursor pages, found by `take_page`.
+    next: Option<Keyset>,
+    prev: Option<Keyset>,
+}
+
+impl PageRequest {
+    /// Reads `limit`, `offset` and `cursor` from the request. An empty `cursor=` starts cursor mode
+    /// at the first page; a cursor is rejected when the other query parameters have changed since it was issued.
+    pub fn from_request(req: &HttpRequest, limit: Option<usize>, offset: Option<usize>, cursor: Option<&str>) -> Result<Self, ApiError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(ApiError::invalid("limit", &format!("must be between 1 and {}", MAX_LIMIT)));
+        }
+
+        let fingerprint = query_fingerprint(&filter_params(req));
+        let cursor = match cursor {
+            None => None,
+            Some(_) if offset.is_some() => {
+                return Err(ApiError::invalid("cursor", "cannot be combined with offset"));
+            },
+            Some(c) => Some(c),
+        };
+
+        let (offset, keyset, mode) = match cursor {
+            None => (offset.unwrap_or(0), None, PageMode::Offset),
+            Some("") => (0, None, PageMode::Cursor),
+            Some(token) => {
+                let decoded = URL_SAFE_NO_PAD
+                    .decode(token)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<CursorToken>(&bytes).ok())
+                    .ok_or_else(|| ApiError::invalid("cursor", "is not a valid cursor"))?;
+                if decoded.q != fingerprint {
+                    return Err(ApiError::invalid("cursor", "was issued for different query parameters"));
+                }
+                (0, Some(decoded.k), PageMode::Cursor)
+            }
+        };
+
+        Ok(PageRequest { limit, offset, keyset, mode, fingerprint, next: None, prev: None })
+    }
+
+    /// Rows to ask the repository for. Cursor mode asks for one more than the page holds,
+    /// to learn whether another page follows without counting.
+    pub fn fetch_limit(&self) -> usize {
+        match self.mode {
+            PageMode::Offset => self.limit,
+            PageMode::Cursor => self.limit + 1,
+        }
+    }
+
+    /// In cursor mode, drops the extra row asked for by [`fetch_limit`](Self::fetch_limit) and
+    /// remembers the keysets of the neighbouring pages for the links.
+    pub fn take_page(&mut self, rows: &mut Vec<CountryModel>, sort: &[SortKey]) {
+        if self.mode != PageMode::Cursor {
+            return;
+        }
+        let backwards = self.keyset.as_ref().is_some_and(|k| k.backwards);
+        let overflow = rows.len() > self.limit;
+        if overflow && backwards {
+            rows.remove(0);
+        } else if overflow {
+            rows.truncate(self.limit);
+        }
+
+        let more_before = if backwards { overflow } else { self.keyset.is_some() };
+        let more_after = if backwards { true } else { overflow };
+        self.prev = rows.first().filter(|_| more_before).map(|row| Keyset::at(row, sort, true));
+        self.next = rows.last().filter(|_| more_after).map(|row| Keyset::at(row, sort, false));
+    }
+
+    /// A link to the page at `offset` in offset mode, or starting from `keyset` in cursor mode.
+    fn link(&self, req: &HttpRequest, offset: usize, keyset: Option<&Keyset>) -> String {
+        let mut params = filter_params(req);
+        params.push(("limit".to_string(), self.limit.to_string()));
+        match (self.mode, keyset) {
+            (PageMode::Offset, _) => params.push(("offset".to_string(), offset.to_string())),
+            (PageMode::Cursor, None) => params.push(("cursor".to_string(), String::new())),
+            (PageMode::Cursor, Some(keyset)) => {
+                let token = CursorToken { k: keyset.clone(), q: self.fingerprint.clone() };
+                let encoded = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&token).unwrap_or_default());
+                params.push(("cursor".to_string(), encoded));
+            }
+        }
+        let query = serde_urlencoded::to_string(&params).unwrap_or_default();
+        format!("{}?{}", req.path(), query)
+    }
+
+    /// Wraps `data` in the paging envelope and adds the matching RFC 8288 `Link` header.
+    pub fn respond(&self, req: &HttpRequest, total: usize, data: Vec<serde_json::Value>) -> HttpResponse {
+        let (next, prev) = match self.mode {
+            PageMode::Offset => (
+                (self.offset + data.len() < total).then(|| self.link(req, self.offset + self.limit, None)),
+                (self.offset > 0).then(|| self.link(req, self.offset.saturating_sub(self.limit), None)),
+            ),
+            PageMode::Cursor => (
+                self.next.as_ref().map(|keyset| self.link(req, 0, Some(keyset))),
+                self.prev.as_ref().map(|keyset| self.link(req, 0, Some(keyset))),
+            ),
+        };
+        let first = self.link(req, 0, None);
+
+        let mut links = vec![format!("<{}>; rel=\"first\"", first)];
+        if let Some(url) = &next {
+            links.push(format!("<{}>; rel=\"next\"", url));
+        }
+        if let Some(url) = &prev {
+            links.push(format!("<{}>; rel=\"prev\"", url));
+        }
+
+        let mut body = serde_json::json!({
+            "data": data,
+            "total": total,
+            "limit": self.limit,
+            "next": next,
+            "prev": prev,
+        });
+        if self.mode == PageMode::Offset {
+            body["offset"] = serde_json::json!(self.offset);
+        }
+
+        HttpResponse::Ok()
+            .insert_header((header::LINK, links.join(", ")))
+            .json(body)
+    }
+}
+
+/// The request's query parameters minus the paging ones, in their original order.
+fn filter_params(req: &HttpRequest) -> Vec<(String, String)> {
+    serde_urlencoded::from_str::<Vec<(String, String)>>(req.query_string())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(key, _)| !PAGING_PARAMS.contains(&key.as_str()))
+        .collect()
+}
+
+/// Order-independent FNV-1a hash of the filter parameters, so a cursor only replays the query it came from.
+fn query_fingerprint(params: &[(String, String)]) -> String {
+    let mut sorted: Vec<String> = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    sorted.sort();
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in sorted.join("&").bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
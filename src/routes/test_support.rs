@@ -0,0 +1,79 @@
+//! Runs handlers against a `memory://` store, for route tests.
+
+use actix_web::{App, http::StatusCode, middleware, test, web};
+use crate::AppState;
+use crate::config::{config::Config, config_scope, db::DbPool};
+use crate::jobs::refresh_jobs::JobRegistry;
+use crate::models::countries_models::Country;
+use crate::models::models::Currency as CurrencyModel;
+use crate::search::suggest_index::SuggestIndex;
+
+/// A refreshed country as the pipeline builds it from the upstream JSON.
+pub fn country(fields: serde_json::Value) -> Country {
+    let mut country = Country::new_from_json(&fields);
+    country.set_last_refreshed_at("2025-11-05T12:00:00.000Z".to_string());
+    country
+}
+
+/// Nigeria with its one currency, as the countries API returns it.
+pub fn nigeria() -> Country {
+    country(serde_json::json!({
+        "name": "Nigeria",
+        "capital": "Abuja",
+        "region": "Africa",
+        "population": 206139587,
+        "currencies": [{ "code": "NGN", "name": "Nigerian naira", "symbol": "₦" }],
+    }))
+}
+
+/// A row of the upstream rate table, against USD.
+pub fn rate(code: &str, rate: f64) -> CurrencyModel {
+    CurrencyModel {
+        code: code.to_string(),
+        name: None,
+        symbol: None,
+        exchange_rate: Some(rate),
+        rate_updated_at: chrono::NaiveDate::from_ymd_opt(2025, 11, 5).unwrap().and_hms_opt(12, 0, 0),
+        rate_base: Some("USD".to_string()),
+    }
+}
+
+/// An in-memory store holding one refresh of `countries` and `rates`.
+pub fn seeded(countries: &[Country], rates: &[CurrencyModel]) -> DbPool {
+    let db = DbPool::connect("memory://");
+    db.upsert_refresh(countries, rates).unwrap();
+    db
+}
+
+/// Sends `GET uri` through the full app and returns the status and JSON body.
+pub async fn get_json(db: &DbPool, uri: &str) -> (StatusCode, serde_json::Value) {
+    let suggest = SuggestIndex::new();
+    suggest.reload(db).await.unwrap();
+    let state = web::Data::new(AppState {
+        env: Config {
+            url: "127.0.0.1".to_string(),
+            port: "0".to_string(),
+            // Nothing listens on the discard port, so a refresh triggered by a test fails fast.
+            countries_api_url: "http://127.0.0.1:9/countries".to_string(),
+            exchange_rate_api_url: "http://127.0.0.1:9/rates".to_string(),
+            refresh_interval_secs: None,
+            refresh_cron: None,
+            run_migrations: false,
+            db: db.clone(),
+        },
+        jobs: JobRegistry::new(),
+        suggest,
+    });
+
+    let app = test::init_service(
+        App::new()
+            .app_data(state)
+            .configure(config_scope::config)
+            .wrap(middleware::from_fn(crate::routes::api_error::negotiate_problem_json)),
+    )
+    .await;
+    let response = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
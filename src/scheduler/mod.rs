@@ -0,0 +1 @@
+pub mod refresh_scheduler;
\ No newline at end of file
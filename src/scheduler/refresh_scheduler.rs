@@ -0,0 +1,81 @@
+use std::str::FromStr;
+use std::time::Duration;
+use cron::Schedule;
+use crate::config::config::Config;
+use crate::jobs::refresh_jobs::{BeginRefreshError, JobRegistry};
+use crate::routes::countries_data::run_refresh_job;
+use crate::search::suggest_index::SuggestIndex;
+
+enum RefreshSchedule {
+    Interval(Duration),
+    Cron(Box<Schedule>),
+}
+
+impl RefreshSchedule {
+    fn from_config(config: &Config) -> Option<Self> {
+        if let Some(expression) = &config.refresh_cron {
+            let schedule = Schedule::from_str(expression)
+                .unwrap_or_else(|e| panic!("REFRESH_CRON is not a valid cron expression ({}): {e}", expression));
+            return Some(RefreshSchedule::Cron(Box::new(schedule)));
+        }
+
+        match config.refresh_interval_secs {
+            Some(0) | None => None,
+            Some(secs) => Some(RefreshSchedule::Interval(Duration::from_secs(secs))),
+        }
+    }
+
+    fn next_delay(&self) -> Option<Duration> {
+        match self {
+            RefreshSchedule::Interval(interval) => Some(*interval),
+            RefreshSchedule::Cron(schedule) => {
+                let now = chrono::Utc::now();
+                let next = schedule.after(&now).next()?;
+                Some((next - now).to_std().unwrap_or(Duration::ZERO))
+            }
+        }
+    }
+}
+
+/// Starts the background refresh loop on the current actix runtime.
+/// `REFRESH_CRON` takes precedence over `REFRESH_INTERVAL_SECS`; with neither set nothing is scheduled.
+pub fn start(config: Config, jobs: JobRegistry, suggest: SuggestIndex) {
+    let schedule = match RefreshSchedule::from_config(&config) {
+        Some(schedule) => schedule,
+        None => {
+            println!("Refresh scheduler disabled (set REFRESH_INTERVAL_SECS or REFRESH_CRON to enable)");
+            return;
+        }
+    };
+
+    match &schedule {
+        RefreshSchedule::Interval(interval) => println!("Refresh scheduler running every {}s", interval.as_secs()),
+        RefreshSchedule::Cron(_) => println!("Refresh scheduler running on cron: {}", config.refresh_cron.clone().unwrap_or_default()),
+    }
+
+    actix_web::rt::spawn(async move {
+        loop {
+            let delay = match schedule.next_delay() {
+                Some(delay) => delay,
+                None => {
+                    println!("Refresh schedule has no upcoming runs, stopping scheduler");
+                    break;
+                }
+            };
+            actix_web::rt::time::sleep(delay).await;
+
+            match jobs.begin(&config.db, "scheduler").await {
+                Ok((job, lock)) => {
+                    println!("Scheduled refresh starting as job {}......", job.id);
+                    let _ = run_refresh_job(&config, &suggest, job, lock).await;
+                },
+                Err(BeginRefreshError::AlreadyRunning(running_id)) => {
+                    println!("Skipping scheduled refresh, job {} is already running", running_id.unwrap_or("unknown".to_string()));
+                },
+                Err(BeginRefreshError::Database(e)) => {
+                    println!("Error starting scheduled refresh: {}", e);
+                }
+            }
+        }
+    });
+}
@@ -7,7 +7,7 @@ diesel::table! {
         file_path -> Varchar,
         total_countries -> Integer,
         top_countries_json -> Text,
-        last_refreshed_at -> Datetime,
+        last_refreshed_at -> Timestamp,
     }
 }
 
@@ -27,8 +27,78 @@ diesel::table! {
         estimated_gdp -> Nullable<Double>,
         #[max_length = 255]
         flag_url -> Nullable<Varchar>,
-        last_refreshed_at -> Nullable<Datetime>,
+        last_refreshed_at -> Nullable<Timestamp>,
+        #[max_length = 2]
+        alpha2_code -> Nullable<Varchar>,
+        #[max_length = 3]
+        alpha3_code -> Nullable<Varchar>,
+        #[max_length = 3]
+        numeric_code -> Nullable<Varchar>,
+        #[max_length = 191]
+        name_key -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    country_aliases (country_id, alias_key) {
+        country_id -> Integer,
+        #[max_length = 191]
+        alias_key -> Varchar,
+    }
+}
+
+diesel::table! {
+    country_currencies (country_id, currency_code) {
+        country_id -> Integer,
+        #[max_length = 32]
+        currency_code -> Varchar,
+        position -> Integer,
+    }
+}
+
+diesel::table! {
+    currencies (code) {
+        #[max_length = 32]
+        code -> Varchar,
+        #[max_length = 191]
+        name -> Nullable<Varchar>,
+        #[max_length = 32]
+        symbol -> Nullable<Varchar>,
+        exchange_rate -> Nullable<Double>,
+        rate_updated_at -> Nullable<Timestamp>,
+        #[max_length = 32]
+        rate_base -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    exchange_rates (id) {
+        id -> Integer,
+        #[max_length = 32]
+        currency_code -> Varchar,
+        rate -> Double,
+        #[max_length = 32]
+        rate_base -> Nullable<Varchar>,
+        fetched_at -> Timestamp,
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(cache_metadata, countries,);
+diesel::table! {
+    refresh_runs (id) {
+        id -> Integer,
+        #[max_length = 36]
+        job_id -> Varchar,
+        #[max_length = 32]
+        triggered_by -> Varchar,
+        #[max_length = 32]
+        status -> Varchar,
+        countries_processed -> Integer,
+        error -> Nullable<Text>,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::joinable!(country_currencies -> currencies (currency_code));
+
+diesel::allow_tables_to_appear_in_same_query!(cache_metadata, countries, country_aliases, country_currencies, currencies, exchange_rates, refresh_runs,);
@@ -0,0 +1 @@
+pub mod suggest_index;
\ No newline at end of file
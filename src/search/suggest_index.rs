@@ -0,0 +1,225 @@
+use std::sync::{Arc, RwLock};
+use serde::Serialize;
+use crate::config::db::{DbPool, DbResult};
+use crate::models::country_names::name_key;
+use crate::models::models::Country as CountryModel;
+use crate::repository::{CountryField, CountryQuery};
+
+/// Columns read when the index is rebuilt; the rest of each row is not needed.
+const INDEXED_FIELDS: [CountryField; 6] = [
+    CountryField::Id,
+    CountryField::Name,
+    CountryField::Capital,
+    CountryField::Alpha2Code,
+    CountryField::Alpha3Code,
+    CountryField::NumericCode,
+];
+
+/// How well a suggestion matched, best first. Suggestions are ranked by tier, then edit distance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Code,
+    Name,
+    NamePrefix,
+    WordPrefix,
+    CapitalPrefix,
+    Fuzzy,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    pub id: i32,
+    pub name: String,
+    pub capital: Option<String>,
+    pub alpha2_code: Option<String>,
+    pub alpha3_code: Option<String>,
+    pub numeric_code: Option<String>,
+    pub matched: MatchKind,
+    #[serde(skip)]
+    distance: usize,
+}
+
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    id: i32,
+    name: String,
+    capital: Option<String>,
+    alpha2_code: Option<String>,
+    alpha3_code: Option<String>,
+    numeric_code: Option<String>,
+    name_key: String,
+    // Folded words of the name, so "sou" finds "Republic of South Africa".
+    word_keys: Vec<String>,
+    capital_key: Option<String>,
+}
+
+/// Country names, capitals and ISO codes held in process memory for autocomplete,
+/// so a keystroke never reaches the database. Rebuilt after every refresh or delete.
+#[derive(Debug, Clone, Default)]
+pub struct SuggestIndex {
+    entries: Arc<RwLock<Vec<IndexEntry>>>,
+}
+
+impl SuggestIndex {
+    pub fn new() -> Self {
+        SuggestIndex::default()
+    }
+
+    /// Reloads the index from every stored country and returns how many it now holds.
+    pub async fn reload(&self, db: &DbPool) -> DbResult<usize> {
+        let query = CountryQuery {
+            fields: Some(INDEXED_FIELDS.to_vec()),
+            limit: i64::MAX,
+            ..CountryQuery::default()
+        };
+        let page = db.run(move |db| db.search_countries(&query)).await?;
+        Ok(self.rebuild(page.countries))
+    }
+
+    fn rebuild(&self, countries: Vec<CountryModel>) -> usize {
+        let entries: Vec<IndexEntry> = countries
+            .into_iter()
+            .filter_map(|c| {
+                let name = c.name?;
+                let word_keys = name
+                    .split(|ch: char| ch.is_whitespace() || ch == '-')
+                    .map(name_key)
+                    .filter(|k| !k.is_empty())
+                    .collect();
+                Some(IndexEntry {
+                    id: c.id,
+                    name_key: name_key(&name),
+                    word_keys,
+                    capital_key: c.capital.as_deref().map(name_key),
+                    name,
+                    capital: c.capital,
+                    alpha2_code: c.alpha2_code,
+                    alpha3_code: c.alpha3_code,
+                    numeric_code: c.numeric_code,
+                })
+            })
+            .collect();
+
+        let count = entries.len();
+        *self.entries.write().unwrap() = entries;
+        count
+    }
+
+    /// Up to `limit` countries matching `q`, best match first.
+    pub fn suggest(&self, q: &str, limit: usize) -> Vec<Suggestion> {
+        let key = name_key(q);
+        if key.is_empty() {
+            return Vec::new();
+        }
+        let code = q.trim().to_ascii_uppercase();
+        // Short prefixes are too ambiguous to correct, so fuzzy matching starts at three characters.
+        let max_distance = match key.chars().count() {
+            0..=2 => 0,
+            3..=5 => 1,
+            _ => 2,
+        };
+
+        let entries = self.entries.read().unwrap();
+        let mut matches: Vec<Suggestion> = entries
+            .iter()
+            .filter_map(|entry| {
+                let (matched, distance) = Self::score(entry, &key, &code, max_distance)?;
+                Some(Suggestion {
+                    id: entry.id,
+                    name: entry.name.clone(),
+                    capital: entry.capital.clone(),
+                    alpha2_code: entry.alpha2_code.clone(),
+                    alpha3_code: entry.alpha3_code.clone(),
+                    numeric_code: entry.numeric_code.clone(),
+                    matched,
+                    distance,
+                })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            a.matched.cmp(&b.matched)
+                .then(a.distance.cmp(&b.distance))
+                .then_with(|| a.name.len().cmp(&b.name.len()))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        matches.truncate(limit);
+        matches
+    }
+
+    fn score(entry: &IndexEntry, key: &str, code: &str, max_distance: usize) -> Option<(MatchKind, usize)> {
+        let codes = [&entry.alpha2_code, &entry.alpha3_code, &entry.numeric_code];
+        if codes.iter().any(|c| c.as_deref() == Some(code)) {
+            return Some((MatchKind::Code, 0));
+        }
+        if entry.name_key == key {
+            return Some((MatchKind::Name, 0));
+        }
+        if entry.name_key.starts_with(key) {
+            return Some((MatchKind::NamePrefix, 0));
+        }
+        if entry.word_keys.iter().any(|w| w.starts_with(key)) {
+            return Some((MatchKind::WordPrefix, 0));
+        }
+        if entry.capital_key.as_deref().is_some_and(|c| c.starts_with(key)) {
+            return Some((MatchKind::CapitalPrefix, 0));
+        }
+        if max_distance == 0 {
+            return None;
+        }
+
+        // Compare against the start of the name as long as the query, so a typo early in a long
+        // name still counts as a near prefix.
+        let prefix: String = entry.name_key.chars().take(key.chars().count()).collect();
+        let distance = strsim::osa_distance(key, &prefix);
+        (distance <= max_distance).then_some((MatchKind::Fuzzy, distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+    use crate::routes::test_support::{country, seeded};
+
+    async fn index() -> SuggestIndex {
+        let db = seeded(&[
+            country(json!({ "name": "Nigeria", "capital": "Abuja", "alpha2Code": "NG", "alpha3Code": "NGA", "numericCode": "566" })),
+            country(json!({ "name": "Niger", "capital": "Niamey", "alpha2Code": "NE", "alpha3Code": "NER" })),
+            country(json!({ "name": "Republic of South Africa", "capital": "Pretoria", "alpha2Code": "ZA" })),
+            country(json!({ "name": "Nicaragua", "capital": "Managua", "alpha2Code": "NI" })),
+            country(json!({ "name": "Peru", "capital": "Lima" })),
+        ], &[]);
+        let index = SuggestIndex::new();
+        assert_eq!(index.reload(&db).await.unwrap(), 5);
+        index
+    }
+
+    fn ranked(suggestions: &[Suggestion]) -> Vec<(&str, MatchKind)> {
+        suggestions.iter().map(|s| (s.name.as_str(), s.matched)).collect()
+    }
+
+    #[actix_web::test]
+    async fn codes_rank_above_name_prefixes() {
+        let index = index().await;
+        assert_eq!(ranked(&index.suggest("ni", 10)), [
+            ("Nicaragua", MatchKind::Code),
+            ("Niger", MatchKind::NamePrefix),
+            ("Nigeria", MatchKind::NamePrefix),
+        ]);
+        assert_eq!(ranked(&index.suggest("566", 10)), [("Nigeria", MatchKind::Code)]);
+        assert_eq!(ranked(&index.suggest("niger", 1)), [("Niger", MatchKind::Name)]);
+    }
+
+    #[actix_web::test]
+    async fn words_capitals_and_typos_match_after_name_prefixes() {
+        let index = index().await;
+        assert_eq!(ranked(&index.suggest("sou", 10)), [("Republic of South Africa", MatchKind::WordPrefix)]);
+        assert_eq!(ranked(&index.suggest("lim", 10)), [("Peru", MatchKind::CapitalPrefix)]);
+        assert_eq!(ranked(&index.suggest("nigre", 10)), [("Niger", MatchKind::Fuzzy), ("Nigeria", MatchKind::Fuzzy)]);
+        // Two letters are too few to correct.
+        assert!(index.suggest("pw", 10).is_empty());
+        assert!(index.suggest(" - ", 10).is_empty());
+    }
+}